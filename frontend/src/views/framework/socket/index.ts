@@ -1,43 +1,36 @@
 import { invoke, Channel } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { getCurrentWindow } from "@tauri-apps/api/window";
-import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow";
 export interface IpcMessage {
   name: string;
   rid: number;
   content: any;
 }
-export async function sendToDeno(value: IpcMessage): Promise<string | null> {
+export async function sendToDeno(value: IpcMessage): Promise<void> {
   return await invoke("plugin:deno|send_to_deno", {
     ...value,
   });
 }
-export async function listenOn(rid: number, name: string): Promise<string> {
-  return await invoke("plugin:deno|listen_on", { rid, name }).then(
-    (r: any) => r
-  );
+export async function listenOn(rid: number, name: string): Promise<void> {
+  return await invoke("plugin:deno|listen_on", { rid, name });
 }
-export async function unlistenFrom(
-  rid: number,
-  name: string
-): Promise<string | null> {
-  return await invoke("plugin:deno|unlisten_from", { rid, name }).then(
-    (r: any) => (r.value ? r.value : null)
-  );
+export async function unlistenFrom(rid: number, name: string): Promise<void> {
+  return await invoke("plugin:deno|unlisten_from", { rid, name });
 }
 
 export async function createDenoChannel(
   key: string,
   channel: Channel<any>
 ): Promise<number> {
+  //demo 的 testIpc 按来源 webview 回复, 需要消息带上来源
   return await invoke("plugin:deno|create_deno_channel", {
     key: key,
     onEvent: channel,
+    withSender: true,
   });
 }
-export async function closeDenoChannel(rid: number): Promise<string> {
-  return await invoke("plugin:deno|close_deno_channel", {
-    payload: { rid },
-  }).then((r: any) => r);
+export async function closeDenoChannel(rid: number): Promise<void> {
+  return await invoke("plugin:deno|close_deno_channel", { rid });
 }
 export async function checkDenoChannel(
   key: string
@@ -47,9 +40,38 @@ export async function checkDenoChannel(
   });
 }
 export async function cleanDenoChannel(
-): Promise<boolean> {
+): Promise<void> {
   return await invoke("plugin:deno|clean_deno_channel", {});
 }
+//deno:// 协议地址 由路径第一段的 worker 以 serve 处理 windows 和 android 上为 http://deno.localhost
+export function denoFetchUrl(worker: string, path: string): string {
+  const base = /Windows|Android/.test(navigator.userAgent) ? "http://deno.localhost" : "deno://localhost";
+  return `${base}/${worker}/${path.replace(/^\//, "")}`;
+}
+export async function denoFetch(worker: string, path: string, init?: RequestInit): Promise<Response> {
+  return await fetch(denoFetchUrl(worker, path), init);
+}
+//询问后端是否允许关闭当前窗口 未设置 close_guard 或确认失败时允许
+//正常关闭时插件已自动确认, 只在页面自行调用 destroy 之前需要
+export async function confirmWindowClose(): Promise<boolean> {
+  try {
+    return await invoke("plugin:deno|confirm_window_close", {});
+  } catch (e) {
+    console.log("confirm window close failed", e);
+    return true;
+  }
+}
+
+//二进制消息以原始帧送达: [事件名长度 u32 大端][序号 u64 大端, 可选][事件名 utf8][数据] 与插件 src/binary.rs 保持一致
+const SEQ_FLAG = 0x80000000;
+function decodeFrame(buffer: ArrayBuffer): { event: string; data: Uint8Array } {
+  const view = new DataView(buffer);
+  const head = view.getUint32(0);
+  const len = head & ~SEQ_FLAG;
+  const offset = head & SEQ_FLAG ? 12 : 4;
+  const event = new TextDecoder().decode(new Uint8Array(buffer, offset, len));
+  return { event, data: new Uint8Array(buffer, offset + len) };
+}
 
 interface ChannelMessage {
   event: String; //对应的事件
@@ -68,6 +90,14 @@ class DenoManager{
        console.log("Clear zombie channel")
         cleanDenoChannel()
       }
+      //worker 重启后插件已关闭旧通道 移除后下次 get 重新创建
+      listen<{ key: string }>("deno:worker-restarted", ({ payload }) => {
+        let deno = manager.get(payload.key);
+        if(deno){
+          deno.invalidate();
+          manager.delete(payload.key);
+        }
+      });
   }
    async get(key: string): Promise<Deno|undefined> {
     if(manager.has(key)){
@@ -95,13 +125,11 @@ class DenoManager{
     }
   }
 }
-getCurrentWebviewWindow().onCloseRequested(async (event) => {
-   await cleanDenoChannel();
-   console.log("denoManager closeAll");
-});
+//窗口关闭由插件询问守护 worker 并释放通道, 页面不再监听 onCloseRequested
+//页面监听 onCloseRequested 时 tauri 会在回调后直接销毁窗口, 绕过守护 worker
 export const denoManager = new DenoManager();
 //deno channe默认实现 主要用于后端的 deno服务的通信
- class Deno extends Channel<ChannelMessage> {
+ class Deno extends Channel<ChannelMessage | ArrayBuffer> {
   #key: string;
   #rid: number = 0;
   #status: "start" | "run" | "close";
@@ -115,7 +143,8 @@ export const denoManager = new DenoManager();
     super();
     this.#key = key;
     this.#status = "start";
-    this.onmessage = (data) => {
+    this.onmessage = (message) => {
+      const data = message instanceof ArrayBuffer ? (({ event, data }) => ({ event, content: data }))(decodeFrame(message)) : message;
       this.arr.forEach((item: any) => {
         if (item.name == data.event) {
           item.fn(data.content);
@@ -168,4 +197,8 @@ export const denoManager = new DenoManager();
     await closeDenoChannel(this.#rid);
     this.#status = "close";
   }
+  //通道已由插件关闭 只更新状态
+  invalidate() {
+    this.#status = "close";
+  }
 }
@@ -1,4 +1,4 @@
-const COMMANDS: &[&str] = &["send_to_deno", "create_deno_channel", "listen_on", "unlisten_from", "close_deno_channel", "clean_deno_channel"];
+const COMMANDS: &[&str] = &["send_to_deno", "send_binary_to_deno", "create_deno_channel", "listen_on", "unlisten_from", "close_deno_channel", "clean_deno_channel", "invoke_deno", "stream_deno", "cancel_deno_stream", "deno_queue_stats", "deno_delivery_stats", "deno_router_health", "publish_topic", "subscribe_topic", "unsubscribe_topic", "ack_deno_channel", "confirm_window_close", "kv_get", "kv_set", "kv_delete", "kv_list"];
 
 fn main() {
   tauri_plugin::Builder::new(COMMANDS).android_path("android").ios_path("ios").build();
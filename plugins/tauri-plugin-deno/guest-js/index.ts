@@ -1,43 +1,114 @@
 import { invoke, Channel } from "@tauri-apps/api/core";
-import { getCurrentWindow } from "@tauri-apps/api/window";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 
 export interface IpcMessage {
   name: string;
   rid: number;
   content: any;
+  seq?: number; //可靠模式下的序号
 }
-export async function sendToDeno(value: IpcMessage): Promise<string | null> {
+//所有命令失败时 promise 以该结构 reject
+export interface DenoError {
+  code:
+    | "io"
+    | "tauri"
+    | "json"
+    | "invalidBinary"
+    | "invalidSchema"
+    | "invalidPayload"
+    | "permissionDenied"
+    | "workerNotFound"
+    | "targetNotFound"
+    | "channelNotFound"
+    | "channelClosed"
+    | "timeout"
+    | "streamNotFound"
+    | "subscriptionNotFound"
+    | "queueFull"
+    | "handlerError"
+    | "kv";
+  message: string;
+  details: any;
+}
+export function isDenoError(e: any): e is DenoError {
+  return typeof e === "object" && e !== null && typeof e.code === "string" && typeof e.message === "string";
+}
+export async function sendToDeno(value: IpcMessage): Promise<void> {
   return await invoke("plugin:deno|send_to_deno", {
     ...value,
   });
 }
-export async function listenOn(rid: number, name: string): Promise<string> {
-  return await invoke("plugin:deno|listen_on", { rid, name }).then(
-    (r: any) => r
-  );
+//二进制帧: [事件名长度 u32 大端][序号 u64 大端, 可选][事件名 utf8][数据] 与 src/binary.rs 保持一致
+//长度最高位为 1 时带序号, 只出现在可靠通道发往 webview 的帧中
+const SEQ_FLAG = 0x80000000;
+export function encodeFrame(event: string, data: Uint8Array): Uint8Array {
+  const name = new TextEncoder().encode(event);
+  const frame = new Uint8Array(4 + name.length + data.length);
+  new DataView(frame.buffer).setUint32(0, name.length);
+  frame.set(name, 4);
+  frame.set(data, 4 + name.length);
+  return frame;
+}
+export function decodeFrame(buffer: ArrayBuffer): { event: string; data: Uint8Array; seq?: number } {
+  const view = new DataView(buffer);
+  const head = view.getUint32(0);
+  const len = head & ~SEQ_FLAG;
+  const offset = head & SEQ_FLAG ? 12 : 4;
+  const seq = head & SEQ_FLAG ? Number(view.getBigUint64(4)) : undefined;
+  const event = new TextDecoder().decode(new Uint8Array(buffer, offset, len));
+  return { event, data: new Uint8Array(buffer, offset + len), seq };
+}
+//tauri 事件只能传递 json, 其中的二进制为 {"$binary": number[]} 与 src/binary.rs 保持一致
+//通道 流式分片 主题消息中的二进制均以原始帧送达, 已经是 Uint8Array
+export function decodeBinary(value: any): Uint8Array | null {
+  if (value && typeof value === "object" && Array.isArray(value.$binary)) {
+    return Uint8Array.from(value.$binary);
+  }
+  return null;
 }
-export async function unlistenFrom(
+//向指定的deno通道发送二进制数据 deno 端收到 Uint8Array
+//可靠模式下 seq 放在 deno-seq 请求头
+export async function sendBinaryToDeno(
   rid: number,
-  name: string
-): Promise<string | null> {
-  return await invoke("plugin:deno|unlisten_from", { rid, name }).then(
-    (r: any) => (r.value ? r.value : null)
-  );
+  name: string,
+  data: Uint8Array | ArrayBuffer,
+  seq?: number
+): Promise<void> {
+  const bytes = data instanceof Uint8Array ? data : new Uint8Array(data);
+  const headers: Record<string, string> = { "deno-rid": String(rid) };
+  if (seq !== undefined) {
+    headers["deno-seq"] = String(seq);
+  }
+  return await invoke("plugin:deno|send_binary_to_deno", encodeFrame(name, bytes), { headers });
+}
+export async function listenOn(rid: number, name: string): Promise<void> {
+  return await invoke("plugin:deno|listen_on", { rid, name });
+}
+export async function unlistenFrom(rid: number, name: string): Promise<void> {
+  return await invoke("plugin:deno|unlisten_from", { rid, name });
 }
 
+//传入 session 时为可靠模式 相同 session 重新创建会恢复监听并重发未确认的消息
+//withSender 为 true 时发往 deno 的消息带上来源 worker 在 listen 回调的 sender 中读取
 export async function createDenoChannel(
   key: string,
-  channel: Channel<any>
+  channel: Channel<any>,
+  session?: string,
+  withSender?: boolean
 ): Promise<number> {
   return await invoke("plugin:deno|create_deno_channel", {
     key: key,
     onEvent: channel,
+    session,
+    withSender,
   });
 }
-export async function closeDenoChannel(rid: number): Promise<string> {
-  return await invoke("plugin:deno|close_deno_channel", {
-    payload: { rid },
-  }).then((r: any) => r);
+//可靠模式下确认 seq 及之前的消息
+export async function ackDenoChannel(rid: number, seq: number): Promise<void> {
+  return await invoke("plugin:deno|ack_deno_channel", { rid, seq });
+}
+export async function closeDenoChannel(rid: number): Promise<void> {
+  return await invoke("plugin:deno|close_deno_channel", { rid });
 }
 export async function checkDenoChannel(
   key: string
@@ -47,13 +118,175 @@ export async function checkDenoChannel(
   });
 }
 export async function cleanDenoChannel(
-): Promise<boolean> {
+): Promise<void> {
   return await invoke("plugin:deno|clean_deno_channel", {});
 }
+//消息队列状态 用于判断 worker 是否在刷屏
+export interface DenoQueueStats {
+  capacity: number;
+  policy: "block" | "dropNewest" | "dropOldest" | "error";
+  depth: number;
+  highWater: number;
+  dropped: number;
+  rejected: number;
+  sources: Record<string, { received: number; dropped: number; rejected: number }>;
+}
+export async function denoQueueStats(): Promise<DenoQueueStats> {
+  return await invoke("plugin:deno|deno_queue_stats", {});
+}
+//路由投递到各 worker 的计数 timedOut 多的 worker 处理不过来
+export interface DenoTargetStats {
+  delivered: number;
+  failed: number;
+  timedOut: number;
+  dropped: number;
+  lastError: string | null;
+}
+export interface DenoDeliveryStats {
+  delivered: number;
+  failed: number;
+  timedOut: number;
+  dropped: number;
+  targets: Record<string, DenoTargetStats>;
+}
+export async function denoDeliveryStats(): Promise<DenoDeliveryStats> {
+  return await invoke("plugin:deno|deno_delivery_stats", {});
+}
+//路由任务状态 restarts 大于 0 说明路由曾经 panic 过
+export interface DenoRouterHealth {
+  running: boolean;
+  restarts: number;
+  startedAt: number | null;
+  lastFailure: string | null;
+  lastFailureAt: number | null;
+}
+export async function denoRouterHealth(): Promise<DenoRouterHealth> {
+  return await invoke("plugin:deno|deno_router_health", {});
+}
+//deno:// 协议地址 由路径第一段的 worker 以 serve 处理 windows 和 android 上为 http://deno.localhost
+export function denoFetchUrl(worker: string, path: string): string {
+  const base = /Windows|Android/.test(navigator.userAgent) ? "http://deno.localhost" : "deno://localhost";
+  return `${base}/${worker}/${path.replace(/^\//, "")}`;
+}
+//响应在 worker 返回全部数据后才可读, response.body 不会逐块到达; 需要流式数据时用 streamDeno
+export async function denoFetch(worker: string, path: string, init?: RequestInit): Promise<Response> {
+  return await fetch(denoFetchUrl(worker, path), init);
+}
+//键值存储 与 worker 共用, 修改后所有窗口和 worker 都会收到 KvChange
+export interface KvEntry {
+  key: string;
+  value: any;
+}
+export interface KvChange {
+  key: string;
+  value: any | null; //删除时为 null, 也可能是写入的 null
+  deleted: boolean; //是否为删除
+  source: string; //发起修改的窗口 label 或 worker key
+}
+export async function kvGet<T = any>(key: string): Promise<T | null> {
+  return await invoke("plugin:deno|kv_get", { key });
+}
+export async function kvSet(key: string, value: any): Promise<void> {
+  return await invoke("plugin:deno|kv_set", { key, value });
+}
+export async function kvDelete(key: string): Promise<boolean> {
+  return await invoke("plugin:deno|kv_delete", { key });
+}
+export async function kvList(prefix?: string): Promise<KvEntry[]> {
+  return await invoke("plugin:deno|kv_list", { prefix });
+}
+//worker 重启后触发 该 worker 的旧通道已关闭, 需重新获取通道并注册监听
+export async function onWorkerRestarted(fn: (key: string) => void): Promise<UnlistenFn> {
+  return await listen<{ key: string }>("deno:worker-restarted", (event) => fn(event.payload.key));
+}
+export async function onKvChange(fn: (change: KvChange) => void): Promise<UnlistenFn> {
+  return await listen<KvChange>("deno:kv-change", (event) => fn(event.payload));
+}
+//询问后端是否允许关闭当前窗口 未设置 close_guard 或确认失败时允许
+//正常关闭时插件已自动确认, 只在页面自行调用 destroy 之前需要
+export async function confirmWindowClose(): Promise<boolean> {
+  try {
+    return await invoke("plugin:deno|confirm_window_close", {});
+  } catch (e) {
+    console.log("confirm window close failed", e);
+    return true;
+  }
+}
+//调用指定 deno 的方法并等待返回 timeout 单位毫秒 失败时以 DenoError reject
+export async function invokeDeno<T = any>(
+  key: string,
+  method: string,
+  args?: any,
+  timeout?: number
+): Promise<T> {
+  return await invoke("plugin:deno|invoke_deno", {
+    key,
+    method,
+    args,
+    timeout,
+  });
+}
+//流式分片 end/error 之后不会再有消息
+export type DenoStreamMessage =
+  | { kind: "chunk"; data: any } //二进制分片为 Uint8Array
+  | { kind: "end" }
+  | { kind: "error"; message: string };
+export interface DenoStream {
+  id: string;
+  cancel: () => Promise<void>; //流已结束或已取消时以 streamNotFound reject
+}
+//向指定 deno 发起流式请求 每个分片按顺序回调
+export async function streamDeno(
+  key: string,
+  method: string,
+  args: any,
+  onMessage: (message: DenoStreamMessage) => void
+): Promise<DenoStream> {
+  const channel = new Channel<DenoStreamMessage | ArrayBuffer>();
+  //二进制分片以原始帧送达 data 为 Uint8Array
+  channel.onmessage = (message) => onMessage(message instanceof ArrayBuffer ? { kind: "chunk", data: decodeFrame(message).data } : message);
+  const id: string = await invoke("plugin:deno|stream_deno", {
+    key,
+    method,
+    args,
+    onChunk: channel,
+  });
+  return { id, cancel: () => invoke("plugin:deno|cancel_deno_stream", { id }) };
+}
+//发布主题消息 retain 为 true 时后续订阅者也能收到 payload 为 null 时清除保留值
+export async function publish(topic: string, payload: any, retain = false): Promise<void> {
+  return await invoke("plugin:deno|publish_topic", { topic, payload, retain });
+}
+//订阅主题 `*` 匹配一级 `**` 匹配任意级 订阅时会先收到匹配的保留消息 返回取消订阅函数
+//重复取消或订阅已因通道关闭被移除时 以 subscriptionNotFound reject
+export async function subscribe(
+  pattern: string,
+  fn: (payload: any, topic: string) => void
+): Promise<() => Promise<void>> {
+  const channel = new Channel<ChannelMessage | ArrayBuffer>();
+  //二进制消息以原始帧送达 帧的事件名为主题
+  channel.onmessage = (message) => {
+    if (message instanceof ArrayBuffer) {
+      const { event, data } = decodeFrame(message);
+      return fn(data, event);
+    }
+    fn(message.content, message.event as string);
+  };
+  const id: string = await invoke("plugin:deno|subscribe_topic", {
+    pattern,
+    onMessage: channel,
+  });
+  return () => invoke("plugin:deno|unsubscribe_topic", { id });
+}
 
 interface ChannelMessage {
   event: String; //对应的事件
   content: any;
+  seq?: number;
+}
+export interface DenoOptions {
+  reliable?: boolean; //可靠模式 消息带序号 确认 重连重发 去重
+  withSender?: boolean; //消息带上来源窗口 默认不带
 }
 interface Litype {
   name: String; //对应的事件
@@ -68,12 +301,20 @@ class DenoManager{
        console.log("Clear zombie channel")
         cleanDenoChannel()
       }
+      //worker 重启后插件已关闭旧通道 移除后下次 get 重新创建
+      listen<{ key: string }>("deno:worker-restarted", ({ payload }) => {
+        let deno = manager.get(payload.key);
+        if(deno){
+          deno.invalidate();
+          manager.delete(payload.key);
+        }
+      });
   }
-   async get(key: string): Promise<Deno|undefined> {
+   async get(key: string, options?: DenoOptions): Promise<Deno|undefined> {
     if(manager.has(key)){
       return manager.get(key);
     }
-    let deno = await Deno.create(key);
+    let deno = await Deno.create(key, options);
     if(!deno.rid){
       console.log("deno instance undefined");
       return undefined;
@@ -95,42 +336,90 @@ class DenoManager{
     }
   }
 }
-getCurrentWindow().onCloseRequested(async (event) => {
-   await cleanDenoChannel();
-   console.log("denoManager closeAll");
-});
+//窗口关闭由插件询问守护 worker 并释放通道, 页面不再监听 onCloseRequested
+//页面监听 onCloseRequested 时 tauri 会在回调后直接销毁窗口, 绕过守护 worker
 export const denoManager = new DenoManager();
 //deno channe默认实现 主要用于后端的 deno服务的通信
- class Deno extends Channel<ChannelMessage> {
+ class Deno extends Channel<ChannelMessage | ArrayBuffer> {
   #key: string;
   #rid: number = 0;
   #status: "start" | "run" | "close";
+  #session?: string;
+  #withSender: boolean;
+  #acked: number = 0; //已确认的连续序号
+  #handled: Set<number> = new Set(); //#acked 之后已处理的序号
+  #pending: { event: String; content: any; seq?: number }[] = []; //还没有监听的消息
+  #sendSeq: number = 0;
   arr: Litype[] = [];
-  static async create(key: string): Promise<Deno> {
-    let deno = new Deno(key);
+  static async create(key: string, options?: DenoOptions): Promise<Deno> {
+    let deno = new Deno(key, options);
     await deno.init();
     return deno;
   }
-  constructor(key: string) {
+  constructor(key: string, options?: DenoOptions) {
     super();
     this.#key = key;
     this.#status = "start";
-    this.onmessage = (data) => {
-      this.arr.forEach((item: any) => {
-        if (item.name == data.event) {
-          item.fn(data.content);
-        }
-      });
+    this.#withSender = options?.withSender ?? false;
+    if (options?.reliable) {
+      //session 保存在 sessionStorage 页面刷新后恢复同一会话
+      //插件在整个会话内按序号去重, 发送序号也一并保存 刷新后继续递增
+      const storageKey = `deno-session-${key}`;
+      this.#session = sessionStorage.getItem(storageKey) ?? crypto.randomUUID();
+      sessionStorage.setItem(storageKey, this.#session);
+      this.#sendSeq = Number(sessionStorage.getItem(this.#seqKey) ?? 0);
+      this.#acked = Number(sessionStorage.getItem(this.#ackKey) ?? 0);
+    }
+    this.onmessage = (message: ChannelMessage | ArrayBuffer) => {
+      //二进制消息以原始帧送达
+      const data =
+        message instanceof ArrayBuffer
+          ? (({ event, data, seq }) => ({ event, content: data, seq }))(decodeFrame(message))
+          : message;
+      this.#dispatch(data);
     };
   }
+  //重发的消息按序号去重 可靠模式下没有监听的消息先缓存, 注册监听后再处理
+  //只有处理过的消息才确认, 确认按连续处理过的序号进行
+  #dispatch(data: { event: String; content: any; seq?: number }) {
+    const seq = data.seq;
+    if (seq !== undefined && (seq <= this.#acked || this.#handled.has(seq))) return;
+    const listeners = this.arr.filter((item: any) => item.name == data.event);
+    if (!listeners.length) {
+      if (seq !== undefined && !this.#pending.some((item) => item.seq == seq)) {
+        this.#pending.push(data);
+      }
+      return;
+    }
+    listeners.forEach((item: any) => item.fn(data.content));
+    if (seq === undefined) return;
+    this.#handled.add(seq);
+    const acked = this.#acked;
+    while (this.#handled.delete(this.#acked + 1)) {
+      this.#acked++;
+    }
+    if (this.#acked != acked) {
+      sessionStorage.setItem(this.#ackKey, String(this.#acked));
+      this.#ack();
+    }
+  }
+  //通道创建完成之前没有 rid, 等 init 之后再确认
+  #ack() {
+    if (!this.#rid || !this.#acked) return;
+    ackDenoChannel(this.#rid, this.#acked).catch((e) => console.log("ack deno channel failed", e));
+  }
+  get #ackKey() {
+    return `deno-ack-${this.#key}`;
+  }
   get rid(){
     return this.#rid;
   }
   //初始化DenoChannel
   async init(fn?: any) {
     if (this.#status == "start") {
-      this.#rid = await createDenoChannel(this.#key, this);
+      this.#rid = await createDenoChannel(this.#key, this, this.#session, this.#withSender);
       this.#status = "run";
+      this.#ack();
       if (fn) {
         await fn();
       }
@@ -142,7 +431,47 @@ export const denoManager = new DenoManager();
       console.log("deno channel is closed");
       return;
     }
-    return await sendToDeno({ rid: this.#rid, name, content: value });
+    if (!this.#session) {
+      return await sendToDeno({ rid: this.#rid, name, content: value });
+    }
+    return await this.#sendReliable((seq) => sendToDeno({ rid: this.#rid, name, content: value, seq }));
+  }
+  //向deno发送二进制消息
+  async sendBinary(name: string, data: Uint8Array | ArrayBuffer) {
+    if (this.#status == "close") {
+      console.log("deno channel is closed");
+      return;
+    }
+    if (!this.#session) {
+      return await sendBinaryToDeno(this.#rid, name, data);
+    }
+    return await this.#sendReliable((seq) => sendBinaryToDeno(this.#rid, name, data, seq));
+  }
+  get #seqKey() {
+    return `deno-seq-${this.#key}`;
+  }
+  //可靠模式 通道暂时不可用时以相同序号重发 插件按序号去重 其余错误直接抛出
+  async #sendReliable(send: (seq: number) => Promise<void>) {
+    const seq = ++this.#sendSeq;
+    sessionStorage.setItem(this.#seqKey, String(seq));
+    for (let attempt = 0; ; attempt++) {
+      try {
+        return await send(seq);
+      } catch (e) {
+        if (attempt >= 4 || !isDenoError(e) || e.code != "channelClosed") {
+          throw e;
+        }
+      }
+      await new Promise((resolve) => setTimeout(resolve, 200 * (attempt + 1)));
+    }
+  }
+  //调用deno方法并等待返回 二进制结果为 ArrayBuffer
+  async invoke<T = any>(method: string, args?: any, timeout?: number): Promise<T> {
+    return await invokeDeno<T>(this.#key, method, args, timeout);
+  }
+  //流式调用deno方法
+  async stream(method: string, args: any, onMessage: (message: DenoStreamMessage) => void): Promise<DenoStream> {
+    return await streamDeno(this.#key, method, args, onMessage);
   }
   //监听
   async listenOn(name: string, fn: any) {
@@ -153,6 +482,10 @@ export const denoManager = new DenoManager();
     await listenOn(this.#rid, name);
     let id = new Date().getTime();
     this.arr.push({ name, fn ,id });
+    //重发或先到的消息在注册监听后处理
+    const pending = this.#pending.filter((item) => item.event == name).sort((a, b) => (a.seq ?? 0) - (b.seq ?? 0));
+    this.#pending = this.#pending.filter((item) => item.event != name);
+    pending.forEach((item) => this.#dispatch(item));
     return ()=>{this.arr=this.arr.filter(item=>item.id!=id);};
   }
   //解除监听
@@ -168,4 +501,8 @@ export const denoManager = new DenoManager();
     await closeDenoChannel(this.#rid);
     this.#status = "close";
   }
+  //通道已由插件关闭 只更新状态
+  invalidate() {
+    this.#status = "close";
+  }
 }
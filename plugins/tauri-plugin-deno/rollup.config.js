@@ -4,23 +4,25 @@ import { cwd } from 'process'
 import typescript from '@rollup/plugin-typescript'
 
 const pkg = JSON.parse(readFileSync(join(cwd(), 'package.json'), 'utf8'))
+//仓库内直接引用 guest-js 源码, 发布时 pnpm 以 publishConfig 中的 dist-js 为入口
+const exports = pkg.publishConfig.exports
 
 export default {
   input: 'guest-js/index.ts',
   output: [
     {
-      file: pkg.exports.import,
+      file: exports.import,
       format: 'esm'
     },
     {
-      file: pkg.exports.require,
+      file: exports.require,
       format: 'cjs'
     }
   ],
   plugins: [
     typescript({
       declaration: true,
-      declarationDir: `./${pkg.exports.import.split('/')[0]}`
+      declarationDir: `./${exports.import.split('/')[0]}`
     })
   ],
   external: [
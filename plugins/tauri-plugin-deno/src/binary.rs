@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use tauri::ipc::InvokeResponseBody;
+
+/// 二进制数据只有一种表示: 通道上为原始字节帧 (见 encode_frame), 不做 base64 编码
+/// deno_ipc 和 tauri 事件只能传递 serde_json::Value, 经过它们时为 {"$binary": [u8...]}
+/// worker-js 的 encodeBinary/decodeBinary 及 guest-js 的 decodeBinary 负责与 Uint8Array 互转
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BinaryPayload {
+  #[serde(rename = "$binary", with = "serde_bytes")]
+  pub data: Vec<u8>,
+}
+
+impl BinaryPayload {
+  pub fn new(data: Vec<u8>) -> Self {
+    Self { data }
+  }
+
+  pub fn into_value(self) -> serde_json::Value {
+    serde_json::to_value(self).unwrap_or_default()
+  }
+
+  /// 只有形如 {"$binary": [...]} 的对象才视为二进制
+  pub fn from_value(value: &serde_json::Value) -> Option<Self> {
+    match value {
+      serde_json::Value::Object(map) if map.len() == 1 && map.contains_key("$binary") => serde_json::from_value(value.clone()).ok(),
+      _ => None,
+    }
+  }
+}
+
+/// 长度字段最高位为 1 时 长度之后是 u64 大端的序号, 用于可靠通道
+const SEQ_FLAG: u32 = 0x8000_0000;
+
+/// webview 与插件之间的二进制帧: [事件名长度 u32 大端][序号 u64 大端, 可选][事件名 utf8][数据]
+pub fn encode_frame(event: &str, data: &[u8], seq: Option<u64>) -> Vec<u8> {
+  let mut frame = Vec::with_capacity(12 + event.len() + data.len());
+  match seq {
+    Some(seq) => {
+      frame.extend_from_slice(&(event.len() as u32 | SEQ_FLAG).to_be_bytes());
+      frame.extend_from_slice(&seq.to_be_bytes());
+    }
+    None => frame.extend_from_slice(&(event.len() as u32).to_be_bytes()),
+  }
+  frame.extend_from_slice(event.as_bytes());
+  frame.extend_from_slice(data);
+  frame
+}
+
+pub fn decode_frame(frame: &[u8]) -> Option<(String, &[u8])> {
+  let len = u32::from_be_bytes(frame.get(..4)?.try_into().ok()?) as usize;
+  let event = frame.get(4..4 + len)?;
+  let event = String::from_utf8(event.to_vec()).ok()?;
+  Some((event, &frame[4 + len..]))
+}
+
+/// 推送到 webview channel 的消息体, 二进制内容以原始帧发送, 其余为 json
+/// 可靠通道带上 seq
+pub fn channel_body(event: &str, content: &serde_json::Value, seq: Option<u64>) -> InvokeResponseBody {
+  match BinaryPayload::from_value(content) {
+    Some(payload) => InvokeResponseBody::Raw(encode_frame(event, &payload.data, seq)),
+    None => InvokeResponseBody::Json(
+      serde_json::to_string(&crate::commands::ChannelMessage {
+        event: event.to_string(),
+        content: content.clone(),
+        seq,
+      })
+      .unwrap_or_default(),
+    ),
+  }
+}
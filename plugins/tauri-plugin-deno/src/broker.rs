@@ -0,0 +1,148 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::ipc::Channel;
+
+use crate::{binary::channel_body, fanout::Fanout, registry::WorkersRegistry};
+
+/// deno -> 插件 发布消息 {topic, payload, retain}
+pub const PUBLISH_EVENT: &str = "deno:publish";
+/// deno -> 插件 订阅 {id, pattern} 订阅者为发出消息的 worker
+pub const SUBSCRIBE_EVENT: &str = "deno:subscribe";
+/// deno -> 插件 取消订阅 {id}
+pub const UNSUBSCRIBE_EVENT: &str = "deno:unsubscribe";
+/// 插件 -> deno 推送订阅消息 {id, topic, payload}
+pub const TOPIC_EVENT: &str = "deno:topic";
+
+/// 主题以 `.` 分级, 订阅时 `*` 匹配一级, `**` 匹配任意级(包括零级)
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+  fn matches(pattern: &[&str], topic: &[&str]) -> bool {
+    match (pattern.first(), topic.first()) {
+      (None, None) => true,
+      (Some(&"**"), _) => matches(&pattern[1..], topic) || (!topic.is_empty() && matches(pattern, &topic[1..])),
+      (Some(&"*"), Some(_)) => matches(&pattern[1..], &topic[1..]),
+      (Some(p), Some(t)) => p == t && matches(&pattern[1..], &topic[1..]),
+      _ => false,
+    }
+  }
+  let pattern: Vec<&str> = pattern.split('.').collect();
+  let topic: Vec<&str> = topic.split('.').collect();
+  matches(&pattern, &topic)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PublishMessage {
+  pub topic: String,
+  #[serde(default)]
+  pub payload: serde_json::Value,
+  #[serde(default)]
+  pub retain: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WorkerSubscription {
+  pub id: String,
+  pub pattern: String,
+}
+
+/// 订阅者 webview 通过 channel 接收, deno worker 以 key 经 fanout 投递
+#[derive(Clone)]
+pub enum Subscriber {
+  Channel(Channel),
+  Worker(String),
+}
+
+struct Subscription {
+  pattern: String,
+  subscriber: Subscriber,
+}
+
+/// 主题消息代理
+/// subscriptions 以订阅 id 保存订阅者, retained 保存各主题最后一次保留的消息
+/// 发往 worker 的消息进入该 worker 的投递通道, 路由不等待 worker 接收
+#[derive(Clone)]
+pub struct Broker {
+  subscriptions: Arc<Mutex<HashMap<String, Subscription>>>,
+  retained: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+  fanout: Fanout,
+  workers: WorkersRegistry,
+}
+
+impl Broker {
+  pub fn new(fanout: Fanout, workers: WorkersRegistry) -> Self {
+    Self {
+      subscriptions: Default::default(),
+      retained: Default::default(),
+      fanout,
+      workers,
+    }
+  }
+
+  /// 订阅 并立即推送匹配的保留消息
+  pub fn subscribe(&self, id: String, pattern: String, subscriber: Subscriber) {
+    let retained: Vec<(String, serde_json::Value)> = self
+      .retained
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|(topic, _)| topic_matches(&pattern, topic))
+      .map(|(topic, payload)| (topic.clone(), payload.clone()))
+      .collect();
+    self.subscriptions.lock().unwrap().insert(id.clone(), Subscription { pattern, subscriber: subscriber.clone() });
+    for (topic, payload) in retained {
+      if !self.deliver(&id, &subscriber, &topic, payload) {
+        self.unsubscribe(&id);
+        return;
+      }
+    }
+  }
+
+  pub fn unsubscribe(&self, id: &str) -> bool {
+    self.subscriptions.lock().unwrap().remove(id).is_some()
+  }
+
+  /// worker 注销或被替换时移除它的全部订阅 新的 worker 需要重新订阅
+  pub fn remove_worker(&self, key: &str) {
+    self.subscriptions.lock().unwrap().retain(|_, subscription| !matches!(&subscription.subscriber, Subscriber::Worker(worker) if worker == key));
+  }
+
+  /// 发布消息 retain 为 true 时保存为该主题的当前值, payload 为 null 时清除
+  pub fn publish(&self, message: PublishMessage) {
+    let PublishMessage { topic, payload, retain } = message;
+    if retain {
+      let mut retained = self.retained.lock().unwrap();
+      if payload.is_null() {
+        retained.remove(&topic);
+      } else {
+        retained.insert(topic.clone(), payload.clone());
+      }
+    }
+    let targets: Vec<(String, Subscriber)> = self
+      .subscriptions
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|(_, subscription)| topic_matches(&subscription.pattern, &topic))
+      .map(|(id, subscription)| (id.clone(), subscription.subscriber.clone()))
+      .collect();
+    for (id, subscriber) in targets {
+      if !self.deliver(&id, &subscriber, &topic, payload.clone()) {
+        self.unsubscribe(&id);
+      }
+    }
+  }
+
+  /// 投递失败返回 false, 由调用方移除订阅; worker 只在已注销时失败, 投递结果计入 fanout 统计
+  fn deliver(&self, id: &str, subscriber: &Subscriber, topic: &str, payload: serde_json::Value) -> bool {
+    match subscriber {
+      Subscriber::Channel(channel) => channel.send(channel_body(topic, &payload, None)).is_ok(),
+      Subscriber::Worker(key) => {
+        let content = serde_json::json!({ "id": id, "topic": topic, "payload": payload });
+        self.fanout.send(&self.workers.snapshot(), key, TOPIC_EVENT.to_string(), content)
+      }
+    }
+  }
+}
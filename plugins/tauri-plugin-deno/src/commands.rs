@@ -1,7 +1,10 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use serde::{Deserialize, Serialize};
-use tauri::{ipc::Channel, Emitter, Manager, Resource, ResourceId, Runtime};
+use tauri::{
+  ipc::{Channel, CommandScope, GlobalScope, InvokeBody, InvokeResponseBody, Request, Response},
+  Emitter, Manager, Resource, ResourceId, Runtime,
+};
 
 use deno_lib::deno_ipc::{
   events_manager::EventsManager,
@@ -16,23 +19,47 @@ use tokio::{
 };
 use uuid::Uuid;
 
-use crate::DenoExt;
+use crate::{
+  binary::{decode_frame, BinaryPayload},
+  broker::{PublishMessage, Subscriber},
+  envelope::{self, MessageSender},
+  fanout::DeliveryStats,
+  queue::QueueStats,
+  reliable::ReliableState,
+  rpc::DEFAULT_TIMEOUT_MS,
+  schema::Direction,
+  scope::{check_reserved, DenoScope, ScopeEntry},
+  subscriptions::{ChannelSink, ChannelSubscriptions},
+  supervisor::RouterHealth,
+  DenoExt,
+};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct ChannelMessage {
   pub event: String, //对应的事件
   pub content: serde_json::Value,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub seq: Option<u64>, //可靠模式下的序号
 }
 type ResouceMap = Arc<Mutex<HashMap<String, Sender<bool>>>>;
 //DenoResource 通信默认实现
+//session 不为空时为可靠模式 消息经 reliable 带序号投递
+//label 所属窗口, 监听的事件登记到 channels, deno 发往该窗口的同名消息也经 sink 投递
+//with_sender 为 true 时发往 deno 的消息带上来源外层 {$sender,$content}
 struct DenoResource {
+  pub key: String,
+  pub label: String,
+  pub with_sender: bool,
   pub events_manager: EventsManager,
-  pub on_event: Channel<ChannelMessage>,
+  pub sink: ChannelSink,
+  pub channels: ChannelSubscriptions,
   pub resouce_map: ResouceMap,
+  pub session: Option<String>,
+  pub reliable: Option<Arc<ReliableState>>,
 }
 impl DenoResource {
   //事件监听
-  async fn listen_on(&self, name: String) {
+  async fn listen_on(&self, rid: ResourceId, name: String) {
     let mut map = self.resouce_map.lock().await;
     if map.contains_key(&name) {
       return;
@@ -41,32 +68,53 @@ impl DenoResource {
     let (listener, mut receiver) = channel(1);
     let (resource_sender, mut resource_receiver) = channel::<bool>(1);
     let events_manager_ref = self.events_manager.clone();
-    let on_event_ref: Channel<ChannelMessage> = self.on_event.clone();
+    let sink_ref = self.sink.clone();
+    let channels_ref = self.channels.clone();
+    let key_ref = self.key.clone();
+    let listener_id = Uuid::new_v4();
+    channels_ref.add(&key_ref, &name, listener_id, self.label.clone(), rid, self.sink.clone());
     tokio::task::spawn(async move {
-      let listener_id = Uuid::new_v4();
       events_manager_ref.listen_on(name.clone(), listener_id, listener).await;
       loop {
         select! {
             value = receiver.recv() => {
-                let result = on_event_ref.send(ChannelMessage{event:name.clone(),content:value.unwrap()});
-                if let Err(e) = result{
-                  println!("send_message_to_deno_error:{:?}",e);
-                    events_manager_ref.unlisten_from(name.clone(), listener_id).await;
-                    break;
+                //可靠模式下发送失败的消息保留在 outbox 等待重连 不取消监听
+                //其他窗口发给 deno 的消息带有来源外层 转给 webview 时去掉
+                let Some(value) = value else { break };
+                if !sink_ref.send(name.clone(), envelope::unwrap(value)) {
+                  println!("send_message_to_deno_error:{}", name);
+                  break;
                 }
             },
             _ = resource_receiver.recv() => {
-                events_manager_ref.unlisten_from(name.clone(), listener_id).await;
                 break;
             }
         }
       }
+      channels_ref.remove(&key_ref, &name, &listener_id);
+      events_manager_ref.unlisten_from(name.clone(), listener_id).await;
     });
     map.insert(name_ref, resource_sender);
   }
-  //发送消息
-  async fn send_message(&self, event: String, message: serde_json::Value) {
-    let _ = self.events_manager.send(event, message).await;
+  //发送消息 返回是否已交给 deno
+  async fn send_message(&self, event: String, message: serde_json::Value) -> bool {
+    self.events_manager.send(event, message).await.is_ok()
+  }
+  //可靠模式下按 seq 去重 交给 deno 之后才记录, 返回 channelClosed 时 webview 以相同 seq 重发
+  async fn send_with_seq(&self, rid: ResourceId, event: String, message: serde_json::Value, seq: Option<u64>) -> crate::Result<()> {
+    let reliable = self.reliable.as_ref().zip(seq);
+    if let Some((reliable, seq)) = reliable {
+      if reliable.is_received(seq) {
+        return Ok(());
+      }
+    }
+    if !self.send_message(event, message).await {
+      return Err(crate::Error::ChannelClosed(rid));
+    }
+    if let Some((reliable, seq)) = reliable {
+      reliable.received(seq);
+    }
+    Ok(())
   }
   //取消监听
   async fn unlisten_from(&self, name: String) {
@@ -76,6 +124,19 @@ impl DenoResource {
     }
   }
 }
+// 从窗口资源表获取通道
+fn deno_resource<R: Runtime>(window: &tauri::WebviewWindow<R>, rid: ResourceId) -> crate::Result<Arc<DenoResource>> {
+  window.resources_table().get::<DenoResource>(rid).map_err(|_| crate::Error::ChannelNotFound(rid))
+}
+// 获取指定 worker 的事件管理器
+fn worker_events_manager<R: Runtime>(window: &tauri::WebviewWindow<R>, key: &str) -> crate::Result<EventsManager> {
+  window.workers_table().events_manager(key).ok_or_else(|| crate::Error::WorkerNotFound(key.to_string()))
+}
+// 窗口是否持有指定 worker 的通道
+pub(crate) fn has_channel_to<R: Runtime>(window: &tauri::WebviewWindow<R>, key: &str) -> bool {
+  let resources_table = window.resources_table();
+  resources_table.names().any(|(rid, name)| name == "deno_resource" && resources_table.get::<DenoResource>(rid).is_ok_and(|channel| channel.key == key))
+}
 impl Resource for DenoResource {
   fn name(&self) -> std::borrow::Cow<'_, str> {
     std::borrow::Cow::Borrowed("deno_resource")
@@ -83,36 +144,70 @@ impl Resource for DenoResource {
 
   fn close(self: std::sync::Arc<Self>) {}
 }
-/// 向所有deno 发送消息
+/// 向所有deno 发送消息 with_sender 为 true 时带上来源外层
 #[tauri::command]
-pub async fn send_to_all_deno<R: Runtime>(window: tauri::WebviewWindow<R>, name: String, content: serde_json::Value) {
-  let w_ref = window.sender();
-  let _ = w_ref.send(IpcMessage::SentToDeno(SentToDenoMessage { id: "".to_string(), event: name, content })).await;
+pub async fn send_to_all_deno<R: Runtime>(window: tauri::WebviewWindow<R>, name: String, content: serde_json::Value, with_sender: Option<bool>, command_scope: CommandScope<ScopeEntry>, global_scope: GlobalScope<ScopeEntry>) -> crate::Result<()> {
+  check_reserved(&name)?;
+  //广播要求对所有 worker 都有权限
+  let scope = DenoScope::new(command_scope, global_scope);
+  let keys: Vec<String> = window.workers_table().keys();
+  for key in keys {
+    scope.check(&key, Some(&name))?;
+  }
+  window.deno().schemas.validate(&name, Direction::ToDeno, &content)?;
+  let content = MessageSender::wrap_if(with_sender.unwrap_or_default(), window.as_ref(), None, content);
+  //直接写入队列, error 策略下队列满时把 queueFull 返回给 webview
+  window.queue().push("host", IpcMessage::SentToDeno(SentToDenoMessage { id: "".to_string(), event: name, content })).await
 }
 
 // Deno命令 向指定的deno 发送消息
+// 可靠模式下带上 seq, 重复的消息直接确认不再投递; 返回 channelClosed 时 webview 应重发
 #[tauri::command]
-pub async fn send_to_deno<R: Runtime>(window: tauri::WebviewWindow<R>, name: String, rid: ResourceId, content: serde_json::Value) {
-  let channel = window.resources_table().get::<DenoResource>(rid);
-  match channel {
-    Ok(channel) => {
-      channel.send_message(name, content).await;
-    }
-    Err(_) => {}
-  }
+pub async fn send_to_deno<R: Runtime>(window: tauri::WebviewWindow<R>, name: String, rid: ResourceId, content: serde_json::Value, seq: Option<u64>, command_scope: CommandScope<ScopeEntry>, global_scope: GlobalScope<ScopeEntry>) -> crate::Result<()> {
+  check_reserved(&name)?;
+  window.deno().schemas.validate(&name, Direction::ToDeno, &content)?;
+  let channel = deno_resource(&window, rid)?;
+  DenoScope::new(command_scope, global_scope).check(&channel.key, Some(&name))?;
+  let content = MessageSender::wrap_if(channel.with_sender, window.as_ref(), Some(rid), content);
+  channel.send_with_seq(rid, name, content, seq).await
 }
+// Deno命令 向指定的deno 发送二进制消息
+// 请求体为原始字节帧 [事件名长度 u32 大端][事件名][数据], 通道 rid 放在 deno-rid 请求头
+// 可靠模式下 seq 放在 deno-seq 请求头, 与 send_to_deno 一样去重
 #[tauri::command]
-pub fn check_deno_channel<R: Runtime>(window: tauri::WebviewWindow<R>, key: String) -> bool {
-  let w_ref: std::sync::Arc<tokio::sync::RwLock<HashMap<String, crate::WorkerManager>>> = window.workers_table();
-  let workers_table: tokio::sync::RwLockReadGuard<'_, HashMap<String, crate::WorkerManager>> = w_ref.try_read().unwrap();
-  match workers_table.get(&key) {
-    Some(_) => true,
-    None => false,
-  }
+pub async fn send_binary_to_deno<R: Runtime>(window: tauri::WebviewWindow<R>, request: Request<'_>, command_scope: CommandScope<ScopeEntry>, global_scope: GlobalScope<ScopeEntry>) -> crate::Result<()> {
+  let InvokeBody::Raw(frame) = request.body() else {
+    return Err(crate::Error::InvalidBinary("expected a raw request body".into()));
+  };
+  let (event, data) = decode_frame(frame).ok_or_else(|| crate::Error::InvalidBinary("malformed frame".into()))?;
+  check_reserved(&event)?;
+  let rid = request
+    .headers()
+    .get("deno-rid")
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.parse::<ResourceId>().ok())
+    .ok_or_else(|| crate::Error::InvalidBinary("missing deno-rid header".into()))?;
+  let seq = match request.headers().get("deno-seq") {
+    Some(v) => Some(v.to_str().ok().and_then(|v| v.parse::<u64>().ok()).ok_or_else(|| crate::Error::InvalidBinary("invalid deno-seq header".into()))?),
+    None => None,
+  };
+  let channel = deno_resource(&window, rid)?;
+  DenoScope::new(command_scope, global_scope).check(&channel.key, Some(&event))?;
+  let content = MessageSender::wrap_if(channel.with_sender, window.as_ref(), Some(rid), BinaryPayload::new(data.to_vec()).into_value());
+  channel.send_with_seq(rid, event, content, seq).await
+}
+#[tauri::command]
+pub async fn check_deno_channel<R: Runtime>(window: tauri::WebviewWindow<R>, key: String) -> crate::Result<bool> {
+  Ok(window.workers_table().contains(&key))
 }
 
 #[tauri::command]
-pub async fn clean_deno_channel<R: Runtime>(window: tauri::WebviewWindow<R>) {
+pub async fn clean_deno_channel<R: Runtime>(window: tauri::WebviewWindow<R>) -> crate::Result<()> {
+  release_channels(&window).await;
+  Ok(())
+}
+// 释放窗口的所有通道 窗口关闭时由插件调用
+pub(crate) async fn release_channels<R: Runtime>(window: &tauri::WebviewWindow<R>) {
   let mut ids = Vec::new();
   for (id, name) in window.resources_table().names() {
     if name.eq("deno_resource") {
@@ -121,63 +216,241 @@ pub async fn clean_deno_channel<R: Runtime>(window: tauri::WebviewWindow<R>) {
   }
   println!("clean_deno_channel count:{:?}", ids.len());
   for id in ids {
-    let deno_channel = window.resources_table().take::<DenoResource>(id);
-    match deno_channel {
-      Ok(c) => {
-        let map = c.resouce_map.lock().await;
-        for (_, v) in map.iter() {
-          let _ = v.send(true).await;
-        }
-      }
-      Err(_) => {}
+    let Ok(c) = window.resources_table().take::<DenoResource>(id) else {
+      continue;
+    };
+    //可靠通道保留监听 等待以相同 session 重新连接
+    if let Some(reliable) = &c.reliable {
+      reliable.detach();
+      continue;
+    }
+    let map = c.resouce_map.lock().await;
+    for (_, v) in map.iter() {
+      let _ = v.send(true).await;
     }
   }
 }
-// 于指定的deno 创建通道
-#[tauri::command]
-pub fn create_deno_channel<R: Runtime>(webview: tauri::WebviewWindow<R>, key: String, on_event: Channel<ChannelMessage>) -> ResourceId {
-  let w_ref = webview.workers_table();
-  let workers_table: tokio::sync::RwLockReadGuard<'_, HashMap<String, crate::WorkerManager>> = w_ref.try_read().unwrap();
-  if let Some(worker_manager) = workers_table.get(&key) {
-    let deno_channel = DenoResource {
-      events_manager: worker_manager.events_manager.clone(),
-      on_event,
-      resouce_map: Arc::new(Mutex::new(HashMap::new())),
+//worker 重启后旧通道仍指向已终止的 worker, 关闭该 worker 的全部通道及可靠会话 页面需重新创建
+pub(crate) async fn release_worker_channels<R: Runtime>(window: &tauri::WebviewWindow<R>, key: &str) {
+  let mut ids = Vec::new();
+  for (id, name) in window.resources_table().names() {
+    if name.eq("deno_resource") && window.resources_table().get::<DenoResource>(id).map_or(false, |c| c.key == key) {
+      ids.push(id);
+    }
+  }
+  for id in ids {
+    let Ok(c) = window.resources_table().take::<DenoResource>(id) else {
+      continue;
     };
-    return webview.resources_table().add(deno_channel);
+    if let Some(session) = &c.session {
+      window.deno().sessions.close(&c.key, session);
+    }
+    let map = c.resouce_map.lock().await;
+    for (_, v) in map.iter() {
+      let _ = v.send(true).await;
+    }
   }
-  0
+}
+// 于指定的deno 创建通道
+// 传入 session 时为可靠模式, 相同 session 重新创建会恢复之前的监听并重发未确认的消息
+// with_sender 为 true 时该通道发往 deno 的消息带上来源外层, 默认不带
+#[tauri::command]
+pub async fn create_deno_channel<R: Runtime>(
+  webview: tauri::WebviewWindow<R>,
+  key: String,
+  on_event: Channel,
+  session: Option<String>,
+  with_sender: Option<bool>,
+  command_scope: CommandScope<ScopeEntry>,
+  global_scope: GlobalScope<ScopeEntry>,
+) -> crate::Result<ResourceId> {
+  DenoScope::new(command_scope, global_scope).check(&key, None)?;
+  let events_manager = worker_events_manager(&webview, &key)?;
+  let (resouce_map, reliable) = match &session {
+    Some(session) => {
+      let reliable_session = webview.deno().sessions.open(&key, session, on_event.clone());
+      (reliable_session.listeners, Some(reliable_session.state))
+    }
+    None => (Arc::new(Mutex::new(HashMap::new())), None),
+  };
+  let deno_channel = DenoResource {
+    key,
+    label: webview.label().to_string(),
+    with_sender: with_sender.unwrap_or_default(),
+    events_manager,
+    sink: ChannelSink::new(on_event, reliable.clone()),
+    channels: webview.deno().channels.clone(),
+    resouce_map,
+    session,
+    reliable,
+  };
+  Ok(webview.resources_table().add(deno_channel))
 }
 // 监听事件
 #[tauri::command]
-pub async fn listen_on<R: Runtime>(window: tauri::WebviewWindow<R>, rid: ResourceId, name: String) {
-  let channel = window.resources_table().get::<DenoResource>(rid).unwrap();
-  channel.listen_on(name.clone()).await;
+pub async fn listen_on<R: Runtime>(window: tauri::WebviewWindow<R>, rid: ResourceId, name: String, command_scope: CommandScope<ScopeEntry>, global_scope: GlobalScope<ScopeEntry>) -> crate::Result<()> {
+  check_reserved(&name)?;
+  let channel = deno_resource(&window, rid)?;
+  DenoScope::new(command_scope, global_scope).check(&channel.key, Some(&name))?;
+  channel.listen_on(rid, name.clone()).await;
+  //可靠通道重新连接后 页面注册监听时才重发未确认的消息
+  if let Some(reliable) = &channel.reliable {
+    reliable.resume();
+  }
+  Ok(())
 }
 // 取消监听
 #[tauri::command]
-pub async fn unlisten_from<R: Runtime>(window: tauri::WebviewWindow<R>, rid: ResourceId, name: String) {
-  let deno_channel = window.resources_table().get::<DenoResource>(rid);
-  match deno_channel {
-    Ok(channel) => {
-      channel.unlisten_from(name).await;
-    }
-    Err(_) => {}
-  }
+pub async fn unlisten_from<R: Runtime>(window: tauri::WebviewWindow<R>, rid: ResourceId, name: String) -> crate::Result<()> {
+  let channel = deno_resource(&window, rid)?;
+  channel.unlisten_from(name).await;
+  Ok(())
 }
 // 关闭通道
 #[tauri::command]
-pub async fn close_deno_channel<R: Runtime>(window: tauri::WebviewWindow<R>, rid: ResourceId) {
-  let deno_channel: Result<Arc<DenoResource>, tauri::Error> = window.resources_table().take::<DenoResource>(rid);
-  match deno_channel {
-    Ok(c) => {
-      tokio::task::spawn(async move {
-        let map = c.resouce_map.lock().await;
-        for (_, v) in map.iter() {
-          let _ = v.send(true).await;
-        }
-      });
+pub async fn close_deno_channel<R: Runtime>(window: tauri::WebviewWindow<R>, rid: ResourceId) -> crate::Result<()> {
+  let c: Arc<DenoResource> = window.resources_table().take::<DenoResource>(rid).map_err(|_| crate::Error::ChannelNotFound(rid))?;
+  if let Some(session) = &c.session {
+    window.deno().sessions.close(&c.key, session);
+  }
+  tokio::task::spawn(async move {
+    let map = c.resouce_map.lock().await;
+    for (_, v) in map.iter() {
+      let _ = v.send(true).await;
     }
-    Err(_) => {}
+  });
+  Ok(())
+}
+// 可靠模式下确认 seq 及之前的消息
+#[tauri::command]
+pub fn ack_deno_channel<R: Runtime>(window: tauri::WebviewWindow<R>, rid: ResourceId, seq: u64) -> crate::Result<()> {
+  let channel = deno_resource(&window, rid)?;
+  if let Some(reliable) = &channel.reliable {
+    reliable.ack(seq);
+  }
+  Ok(())
+}
+// 调用指定 deno 的方法并等待应答 timeout 单位毫秒
+#[tauri::command]
+pub async fn invoke_deno<R: Runtime>(
+  window: tauri::WebviewWindow<R>,
+  key: String,
+  method: String,
+  args: Option<serde_json::Value>,
+  timeout: Option<u64>,
+  command_scope: CommandScope<ScopeEntry>,
+  global_scope: GlobalScope<ScopeEntry>,
+) -> crate::Result<Response> {
+  DenoScope::new(command_scope, global_scope).check(&key, Some(&method))?;
+  let events_manager = worker_events_manager(&window, &key)?;
+  let rpc = window.deno().rpc.clone();
+  let timeout = Duration::from_millis(timeout.unwrap_or(DEFAULT_TIMEOUT_MS));
+  let result = rpc.invoke(&events_manager, key, method, args.unwrap_or_default(), timeout).await?;
+  //二进制结果直接以原始字节返回
+  let body = match BinaryPayload::from_value(&result) {
+    Some(payload) => InvokeResponseBody::Raw(payload.data),
+    None => InvokeResponseBody::Json(result.to_string()),
+  };
+  Ok(Response::new(body))
+}
+// 向指定 deno 发起流式请求 分片按顺序写入 on_chunk 返回请求 id
+#[tauri::command]
+pub async fn stream_deno<R: Runtime>(
+  window: tauri::WebviewWindow<R>,
+  key: String,
+  method: String,
+  args: Option<serde_json::Value>,
+  on_chunk: Channel,
+  command_scope: CommandScope<ScopeEntry>,
+  global_scope: GlobalScope<ScopeEntry>,
+) -> crate::Result<String> {
+  DenoScope::new(command_scope, global_scope).check(&key, Some(&method))?;
+  let events_manager = worker_events_manager(&window, &key)?;
+  let streams = window.deno().streams.clone();
+  streams.start(events_manager, key, method, args.unwrap_or_default(), on_chunk).await
+}
+// 取消流式请求 流不存在或已结束时返回 streamNotFound
+#[tauri::command]
+pub async fn cancel_deno_stream<R: Runtime>(window: tauri::WebviewWindow<R>, id: String) -> crate::Result<()> {
+  let streams = window.deno().streams.clone();
+  if !streams.cancel(id.clone()) {
+    return Err(crate::Error::StreamNotFound(id));
+  }
+  Ok(())
+}
+// 查询消息队列积压及丢弃情况
+#[tauri::command]
+pub fn deno_queue_stats<R: Runtime>(window: tauri::WebviewWindow<R>) -> crate::Result<QueueStats> {
+  Ok(window.queue().stats())
+}
+// 路由投递到各 worker 的计数 用于排查慢的或已失效的 worker
+#[tauri::command]
+pub fn deno_delivery_stats<R: Runtime>(window: tauri::WebviewWindow<R>) -> crate::Result<DeliveryStats> {
+  Ok(window.deno().fanout.stats())
+}
+// 路由任务状态 restarts 为 panic 后重启的次数
+#[tauri::command]
+pub fn deno_router_health<R: Runtime>(window: tauri::WebviewWindow<R>) -> crate::Result<RouterHealth> {
+  Ok(window.deno().router.health())
+}
+// 询问守护 worker 是否允许关闭 窗口关闭时插件已自动确认, 供页面自行关闭(如 destroy)之前使用
+#[tauri::command]
+pub async fn confirm_window_close<R: Runtime>(window: tauri::WebviewWindow<R>) -> crate::Result<bool> {
+  let deno = window.deno().clone();
+  Ok(deno.confirm_close(window.label()).await)
+}
+// 读取键值 不存在时返回 null
+#[tauri::command]
+pub async fn kv_get<R: Runtime>(window: tauri::WebviewWindow<R>, key: String) -> crate::Result<Option<serde_json::Value>> {
+  let kv = window.deno().kv.clone();
+  kv.get(key).await
+}
+// 写入键值 变更以 deno:kv-change 通知所有窗口和 worker
+#[tauri::command]
+pub async fn kv_set<R: Runtime>(window: tauri::WebviewWindow<R>, key: String, value: serde_json::Value) -> crate::Result<()> {
+  let deno = window.deno().clone();
+  deno.kv_set(window.label(), key, value).await
+}
+// 删除键值 返回键是否存在
+#[tauri::command]
+pub async fn kv_delete<R: Runtime>(window: tauri::WebviewWindow<R>, key: String) -> crate::Result<bool> {
+  let deno = window.deno().clone();
+  deno.kv_delete(window.label(), key).await
+}
+// 按前缀列出键值 按键排序
+#[tauri::command]
+pub async fn kv_list<R: Runtime>(window: tauri::WebviewWindow<R>, prefix: Option<String>) -> crate::Result<Vec<crate::KvEntry>> {
+  let kv = window.deno().kv.clone();
+  kv.list(prefix).await
+}
+// 发布主题消息 retain 为 true 时后续订阅者也能收到
+#[tauri::command]
+pub async fn publish_topic<R: Runtime>(window: tauri::WebviewWindow<R>, topic: String, payload: serde_json::Value, retain: Option<bool>, command_scope: CommandScope<ScopeEntry>, global_scope: GlobalScope<ScopeEntry>) -> crate::Result<()> {
+  check_reserved(&topic)?;
+  DenoScope::new(command_scope, global_scope).check_topic(&topic)?;
+  //主题消息会送到订阅的 worker, 以发往 deno 的 schema 校验
+  window.deno().schemas.validate(&topic, Direction::ToDeno, &payload)?;
+  window.deno().broker.publish(PublishMessage {
+    topic,
+    payload,
+    retain: retain.unwrap_or(false),
+  });
+  Ok(())
+}
+// 订阅主题 支持 * 和 ** 通配 返回订阅 id
+#[tauri::command]
+pub async fn subscribe_topic<R: Runtime>(window: tauri::WebviewWindow<R>, pattern: String, on_message: Channel, command_scope: CommandScope<ScopeEntry>, global_scope: GlobalScope<ScopeEntry>) -> crate::Result<String> {
+  check_reserved(&pattern)?;
+  DenoScope::new(command_scope, global_scope).check_topic(&pattern)?;
+  let id = Uuid::new_v4().to_string();
+  window.deno().broker.subscribe(id.clone(), pattern, Subscriber::Channel(on_message));
+  Ok(id)
+}
+// 取消订阅 订阅不存在时返回 subscriptionNotFound
+#[tauri::command]
+pub fn unsubscribe_topic<R: Runtime>(window: tauri::WebviewWindow<R>, id: String) -> crate::Result<()> {
+  if !window.deno().broker.unsubscribe(&id) {
+    return Err(crate::Error::SubscriptionNotFound(id));
   }
+  Ok(())
 }
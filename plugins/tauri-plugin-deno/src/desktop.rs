@@ -1,89 +1,488 @@
-use std::{collections::HashMap, sync::Arc};
+use std::time::Duration;
 
-use deno_lib::deno_ipc::{messages::IpcMessage, IpcReceiver, IpcSender};
+use deno_lib::deno_ipc::{messages::IpcMessage, IpcSender};
+use serde::{de::DeserializeOwned, Serialize};
 use tauri::{AppHandle, Emitter, Manager, Runtime};
-use tokio::sync::RwLock;
 
-use crate::{models::*, DenoExt};
+use crate::{
+  broker::{Broker, PublishMessage, Subscriber, WorkerSubscription, PUBLISH_EVENT, SUBSCRIBE_EVENT, UNSUBSCRIBE_EVENT},
+  commands::{release_channels, release_worker_channels},
+  extension::WorkerExtensions,
+  fanout::{Fanout, FanoutConfig},
+  fetch::{FetchConfig, FetchManager, FETCH_RESPONSE_EVENT},
+  host::{HostCall, HostCommands, HOST_CALL_EVENT, HOST_REPLY_EVENT},
+  kv::{KvChange, KvOp, KvReply, KvRequest, KvStore, KV_CHANGE_EVENT, KV_EVENT, KV_REPLY_EVENT},
+  lifecycle::{CloseGuard, LifecycleEvent, VisibilityTracker, CLOSE_REQUESTED_METHOD, LIFECYCLE_EVENT, WORKER_RESTARTED_EVENT},
+  models::*,
+  queue::{IpcQueue, QueueConfig},
+  registry::WorkersRegistry,
+  reliable::ReliableSessions,
+  rpc::{RpcManager, DEFAULT_TIMEOUT_MS, REPLY_EVENT},
+  schema::{Direction, SchemaRegistry},
+  stream::{StreamManager, STREAM_CHUNK_EVENT},
+  subscriptions::{ChannelSubscriptions, HostSubscription},
+  supervisor::RouterSupervisor,
+  target::{self, EmitMessage, WindowTarget, EMIT_ERROR_EVENT, EMIT_EVENT},
+  window::{WindowManager, WindowRequest, WINDOW_EVENT, WINDOW_REPLY_EVENT},
+};
 
-pub fn init<R: Runtime>(app: &AppHandle<R>, main_module: String) -> crate::Result<DenoManager<R>> {
-  let deno_manager = DenoManager::new(app.clone(), main_module);
+/// 由 Builder 收集的插件配置
+pub struct DenoConfig {
+  pub main_module: String,
+  pub queue: QueueConfig,
+  pub fanout: FanoutConfig,
+  pub schemas: SchemaRegistry,
+  pub commands: HostCommands,
+  pub windows: WindowManager,
+  pub close_guard: Option<CloseGuard>,
+  pub extensions: WorkerExtensions,
+  pub kv: KvStore,
+  pub fetch: FetchConfig,
+}
+
+pub fn init<R: Runtime>(app: &AppHandle<R>, config: DenoConfig) -> crate::Result<DenoManager<R>> {
+  let deno_manager = DenoManager::new(app.clone(), config);
   let _ = deno_manager.initialize();
   Ok(deno_manager)
 }
 ///deno 插件管理器
-/// workers_table deno 进程的map
+/// workers_table deno 进程的注册表
 /// main_module deno 主进程的模块
-#[derive(Clone)]
+/// queue deno 和 webview 发往路由的有界消息队列, deno_sender 为插件自身的发送端
+/// fanout 路由到各 worker 的并发投递
+/// rpc webview 到 deno 的请求/应答管理
+/// streams webview 到 deno 的流式请求管理
+/// fetches deno:// 协议请求的响应管理
+/// broker 主题消息代理 webview 和 deno 共用
+/// sessions 可靠通道的会话 页面刷新后可恢复
+/// schemas 事件载荷的 json schema 校验
+/// channels webview 通道 listen_on 的订阅 deno 发往窗口的消息优先经通道投递
+/// router 路由任务的守护 panic 后自动重启
+/// commands 宿主注册的 rust 命令 worker 通过 callHost 调用
+/// windows worker 的窗口操作及授权
+/// visibility 窗口整体可见状态 变化时通知 worker
+/// close_guard 窗口关闭前询问的 worker 未设置时直接关闭
+/// extensions 宿主注册的 deno_core 扩展 每个 worker 启动时安装
+/// kv webview 和 worker 共用的键值存储 修改后通知所有窗口和 worker
 pub struct DenoManager<R: Runtime> {
   pub handler: AppHandle<R>,
   pub main_module: String,
   pub deno_sender: IpcSender,
-  pub deno_receiver: IpcReceiver,
-  pub workers_table: Arc<RwLock<HashMap<String, WorkerManager>>>,
+  pub queue: IpcQueue,
+  pub workers_table: WorkersRegistry,
+  pub fanout: Fanout,
+  pub rpc: RpcManager,
+  pub streams: StreamManager,
+  pub fetches: FetchManager,
+  pub broker: Broker,
+  pub sessions: ReliableSessions,
+  pub schemas: SchemaRegistry,
+  pub channels: ChannelSubscriptions,
+  pub router: RouterSupervisor,
+  pub commands: HostCommands,
+  pub windows: WindowManager,
+  pub visibility: VisibilityTracker,
+  pub close_guard: Option<CloseGuard>,
+  pub extensions: WorkerExtensions,
+  pub kv: KvStore,
+}
+//AppHandle 的 Clone 不要求 R: Clone, derive 会多加这个约束
+impl<R: Runtime> Clone for DenoManager<R> {
+  fn clone(&self) -> Self {
+    Self {
+      handler: self.handler.clone(),
+      main_module: self.main_module.clone(),
+      deno_sender: self.deno_sender.clone(),
+      queue: self.queue.clone(),
+      workers_table: self.workers_table.clone(),
+      fanout: self.fanout.clone(),
+      rpc: self.rpc.clone(),
+      streams: self.streams.clone(),
+      fetches: self.fetches.clone(),
+      broker: self.broker.clone(),
+      sessions: self.sessions.clone(),
+      schemas: self.schemas.clone(),
+      channels: self.channels.clone(),
+      router: self.router.clone(),
+      commands: self.commands.clone(),
+      windows: self.windows.clone(),
+      visibility: self.visibility.clone(),
+      close_guard: self.close_guard.clone(),
+      extensions: self.extensions.clone(),
+      kv: self.kv.clone(),
+    }
+  }
 }
 impl<R: Runtime> DenoManager<R> {
-  pub fn new(handler: AppHandle<R>, main_module: String) -> Self {
-    let (deno_sender, deno_receiver) = async_channel::unbounded::<IpcMessage>();
+  pub fn new(handler: AppHandle<R>, config: DenoConfig) -> Self {
+    let DenoConfig {
+      main_module,
+      queue: queue_config,
+      fanout: fanout_config,
+      schemas,
+      commands,
+      windows,
+      close_guard,
+      extensions,
+      kv,
+      fetch,
+    } = config;
+    let queue = IpcQueue::new(queue_config);
+    let deno_sender = queue.sender("host");
+
+    let workers_table = WorkersRegistry::new();
+    let fanout = Fanout::new(fanout_config);
 
     Self {
       handler,
       main_module,
       deno_sender,
-      deno_receiver,
-      workers_table: Arc::new(RwLock::new(HashMap::new())),
+      queue,
+      rpc: RpcManager::new(),
+      streams: StreamManager::new(fanout.clone(), workers_table.clone()),
+      fetches: FetchManager::new(fetch),
+      broker: Broker::new(fanout.clone(), workers_table.clone()),
+      workers_table,
+      fanout,
+      sessions: ReliableSessions::new(),
+      schemas,
+      channels: ChannelSubscriptions::new(),
+      router: RouterSupervisor::new(),
+      commands,
+      windows,
+      visibility: VisibilityTracker::new(),
+      close_guard,
+      extensions,
+      kv,
     }
   }
   ///初始化插件并启动 deno 进程
   pub fn initialize(&self) -> Result<(), Box<dyn std::error::Error>> {
-    let handle_ref = self.handler.clone();
-    let reff = self.workers_table.clone();
-    let main_module_ref = self.main_module.clone();
-    let deno_sender_ref = self.deno_sender.clone();
+    let manager = self.clone();
+    self.router.start(move || run(manager.clone()));
+    let manager = self.clone();
     //初始化主deno线程
     tokio::task::spawn(async move {
-      let key = "main".to_string();
-      reff.write().await.insert(key.clone(), WorkerManager::new(key, main_module_ref, deno_sender_ref));
-      run(handle_ref).await;
+      if let Err(e) = manager.spawn_worker("main", manager.main_module.clone()).await {
+        println!("failed to start main deno worker: {}", e);
+      }
     });
     Ok(())
   }
+  ///启动 worker 并注册 同名的旧 worker 会被终止
+  ///worker 启动期间不占用注册表, 其他命令照常读取
+  pub async fn spawn_worker(&self, key: impl Into<String>, main_module: String) -> crate::Result<()> {
+    let key = key.into();
+    //新 worker 启动期间就可能发出订阅 旧 worker 的订阅要在启动之前移除
+    self.broker.remove_worker(&key);
+    let deno_sender = self.queue.sender(key.clone());
+    let worker_key = key.clone();
+    let extensions = self.extensions.clone();
+    let worker_manager = tokio::task::spawn_blocking(move || WorkerManager::new(worker_key, main_module, deno_sender, extensions))
+      .await
+      .map_err(|e| crate::Error::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))?;
+    if self.workers_table.insert(key.clone(), worker_manager).is_some() {
+      self.rpc.fail_worker(&key);
+      self.release_worker_channels(&key).await;
+    }
+    Ok(())
+  }
+  ///关闭指向被替换 worker 的通道 并通知页面重新创建
+  async fn release_worker_channels(&self, key: &str) {
+    for window in self.handler.webview_windows().into_values() {
+      release_worker_channels(&window, key).await;
+    }
+    if let Err(e) = self.handler.emit(WORKER_RESTARTED_EVENT, serde_json::json!({ "key": key })) {
+      println!("failed to emit worker restarted to windows: {}", e);
+    }
+  }
+  ///注销并终止 worker 不存在时返回 false
+  pub fn terminate_worker(&self, key: &str) -> bool {
+    let removed = self.workers_table.remove(key).is_some();
+    if removed {
+      self.rpc.fail_worker(key);
+      self.broker.remove_worker(key);
+    }
+    removed
+  }
+  ///以原来的模块重新启动 worker 不存在时返回 WorkerNotFound
+  pub async fn restart_worker(&self, key: &str) -> crate::Result<()> {
+    let main_module = self.workers_table.get(key).map(|worker| worker.main_nodule.clone()).ok_or_else(|| crate::Error::WorkerNotFound(key.to_string()))?;
+    self.spawn_worker(key, main_module).await
+  }
+  ///向指定 worker 发送事件 与 webview 经通道发送的消息相同, 只是没有来源外层
+  pub async fn emit_to<T: Serialize>(&self, key: &str, event: &str, payload: T) -> crate::Result<()> {
+    let events_manager = self.workers_table.events_manager(key).ok_or_else(|| crate::Error::WorkerNotFound(key.to_string()))?;
+    let payload = serde_json::to_value(payload)?;
+    events_manager.send(event.to_string(), payload).await.map_err(|_| crate::Error::WorkerNotFound(key.to_string()))?;
+    Ok(())
+  }
+  ///向所有 worker 发送事件 经 fanout 投递不等待
+  pub fn broadcast<T: Serialize>(&self, event: &str, payload: T) -> crate::Result<()> {
+    let payload = serde_json::to_value(payload)?;
+    let workers = self.workers_table.snapshot();
+    for key in workers.keys() {
+      self.fanout.dispatch(&workers, key, event.to_string(), payload.clone());
+    }
+    Ok(())
+  }
+  ///订阅 worker 发往窗口的事件 返回的 stream 释放时取消订阅
+  pub fn subscribe(&self, key: &str, event: &str) -> HostSubscription {
+    self.channels.subscribe_host(key, event)
+  }
+  ///调用 worker 中以 handle 注册的方法 超时时间与 invoke_deno 默认值相同
+  pub async fn call<Req: Serialize, Resp: DeserializeOwned>(&self, key: &str, method: &str, req: Req) -> crate::Result<Resp> {
+    let events_manager = self.workers_table.events_manager(key).ok_or_else(|| crate::Error::WorkerNotFound(key.to_string()))?;
+    let args = serde_json::to_value(req)?;
+    let result = self.rpc.invoke(&events_manager, key.to_string(), method.to_string(), args, Duration::from_millis(DEFAULT_TIMEOUT_MS)).await?;
+    Ok(serde_json::from_value(result)?)
+  }
+  ///写入键值 成功后通知所有窗口和 worker, source 为发起修改的窗口或 worker
+  pub async fn kv_set(&self, source: &str, key: String, value: serde_json::Value) -> crate::Result<()> {
+    let manager = self.clone();
+    let change = KvChange {
+      key: key.clone(),
+      value: Some(value.clone()),
+      deleted: false,
+      source: source.to_string(),
+    };
+    self.kv.set(key, value, move || manager.notify_kv(change)).await
+  }
+  ///删除键值 键不存在时返回 false 且不通知
+  pub async fn kv_delete(&self, source: &str, key: String) -> crate::Result<bool> {
+    let manager = self.clone();
+    let change = KvChange {
+      key: key.clone(),
+      value: None,
+      deleted: true,
+      source: source.to_string(),
+    };
+    self.kv.delete(key, move || manager.notify_kv(change)).await
+  }
+  fn notify_kv(&self, change: KvChange) {
+    if let Err(e) = self.handler.emit(KV_CHANGE_EVENT, &change) {
+      println!("failed to emit kv change to windows: {}", e);
+    }
+    if let Err(e) = self.broadcast(KV_CHANGE_EVENT, &change) {
+      println!("failed to dispatch kv change to workers: {}", e);
+    }
+  }
+  ///执行 worker 的 kv 操作
+  pub async fn kv_handle(&self, source: &str, request: KvRequest) -> KvReply {
+    let KvRequest { id, op } = request;
+    let result = match op {
+      KvOp::Get { key } => self.kv.get(key).await.map(|value| value.unwrap_or_default()),
+      KvOp::Set { key, value } => self.kv_set(source, key, value).await.map(|_| serde_json::Value::Null),
+      KvOp::Delete { key } => self.kv_delete(source, key).await.map(serde_json::Value::Bool),
+      KvOp::List { prefix } => self.kv.list(prefix).await.and_then(|entries| serde_json::to_value(entries).map_err(Into::into)),
+    };
+    match result {
+      Ok(result) => KvReply { id, result: Some(result), error: None },
+      Err(error) => KvReply { id, result: None, error: Some(error) },
+    }
+  }
+  ///向所有 worker 发送生命周期事件 worker 以 onLifecycle 订阅
+  pub fn notify_lifecycle(&self, event: LifecycleEvent) {
+    if let Err(e) = self.broadcast(LIFECYCLE_EVENT, event) {
+      println!("failed to dispatch lifecycle event: {}", e);
+    }
+  }
+  ///询问守护 worker 是否允许关闭窗口 返回 false 时应阻止关闭
+  pub async fn confirm_close(&self, label: &str) -> bool {
+    let Some(CloseGuard { worker, timeout }) = &self.close_guard else {
+      return true;
+    };
+    let Some(events_manager) = self.workers_table.events_manager(worker) else {
+      return true;
+    };
+    let args = serde_json::json!({ "label": label });
+    match self.rpc.invoke(&events_manager, worker.clone(), CLOSE_REQUESTED_METHOD.to_string(), args, *timeout).await {
+      Ok(serde_json::Value::Bool(allow)) => allow,
+      Ok(_) => true,
+      Err(e) => {
+        println!("close guard of window {} failed, closing anyway: {}", label, e);
+        true
+      }
+    }
+  }
+  ///窗口请求关闭 未设置守护 worker 时只释放通道, 窗口照常关闭
+  ///设置了守护 worker 时调用方已阻止本次关闭, 确认允许后释放通道并销毁窗口
+  pub fn guard_close(&self, label: &str) {
+    let Some(window) = self.handler.get_webview_window(label) else {
+      return;
+    };
+    let manager = self.clone();
+    let label = label.to_string();
+    tauri::async_runtime::spawn(async move {
+      let Some(guard) = &manager.close_guard else {
+        release_channels(&window).await;
+        return;
+      };
+      if !guard.begin(&label) {
+        return;
+      }
+      let allow = manager.confirm_close(&label).await;
+      guard.finish(&label);
+      if !allow {
+        return;
+      }
+      release_channels(&window).await;
+      if let Err(e) = window.destroy() {
+        println!("failed to close window {}: {}", label, e);
+      }
+    });
+  }
+  ///处理 deno:// 协议请求 交给路径第一段对应 worker 的 serve 处理函数 webview 为发起请求的 webview label
+  pub async fn fetch(&self, webview: &str, request: tauri::http::Request<Vec<u8>>) -> tauri::http::Response<Vec<u8>> {
+    let events_manager = FetchManager::split_worker(&request).and_then(|(worker, _)| self.workers_table.events_manager(&worker));
+    self.fetches.fetch(webview, events_manager, request).await
+  }
+  ///重新计算窗口可见状态 变化时通知 worker
+  ///读取窗口状态需要主线程处理 在独立任务中执行, 避免在事件回调中阻塞
+  pub fn refresh_visibility(&self) {
+    let manager = self.clone();
+    tauri::async_runtime::spawn(async move {
+      if let Some(event) = manager.visibility.refresh(&manager.handler) {
+        manager.notify_lifecycle(event);
+      }
+    });
+  }
 }
 
 /// deno 插件运行主函数
 /// 通信实现
 /// 1.接收webview发来的消息，通过webview id找到对应的worker，然后通知worker
 /// 2.接收deno发来的消息，通过id找到对应的worker，然后通知worker
-async fn run<R: Runtime>(handle_ref: tauri::AppHandle<R>) {
-  let ipc_recever = handle_ref.receiver();
-  let workers_table_ref = handle_ref.workers_table();
+/// 3.拦截deno的调用应答 流式分片和协议响应，交给对应的管理器
+/// 4.拦截deno的主题发布和订阅，交给broker
+/// 5.执行worker调用的宿主命令 结果回复给调用的worker
+/// 6.执行worker的窗口操作 按授权校验后回复结果 并重新计算窗口可见状态
+/// 7.执行worker的 kv 操作 修改通知所有窗口和 worker
+/// 8.按目标发往窗口 订阅了该事件的通道优先 目标不存在时通知发送的worker 不再退化为广播
+/// 发往 deno 的消息 包括主题消息和流取消通知 都交给 fanout 入队, 路由不等待 worker 接收
+/// 由 router 守护, 退出或 panic 后以同一个队列重新启动
+async fn run<R: Runtime>(manager: DenoManager<R>) {
+  let notifier = manager.clone();
+  let DenoManager {
+    handler: handle_ref,
+    queue,
+    workers_table,
+    fanout,
+    rpc,
+    streams,
+    fetches,
+    broker,
+    schemas,
+    channels,
+    commands,
+    windows,
+    ..
+  } = manager;
   loop {
-    match ipc_recever.recv().await.unwrap() {
-      IpcMessage::SentToWindow(msg) => {
-        let window = handle_ref.get_webview_window(&msg.id);
-        match window {
-          Some(window) => {
-            let _ = window.emit(&msg.event, msg.content);
-          }
-          None => {
-            let _ = handle_ref.emit(&msg.event, msg.content);
+    let (source, message) = queue.recv().await;
+    match message {
+      IpcMessage::SentToWindow(msg) if msg.event == REPLY_EVENT => {
+        rpc.resolve(msg.content);
+      }
+      IpcMessage::SentToWindow(msg) if msg.event == STREAM_CHUNK_EVENT => {
+        streams.dispatch(msg.content);
+      }
+      IpcMessage::SentToWindow(msg) if msg.event == FETCH_RESPONSE_EVENT => {
+        fetches.dispatch(msg.content);
+      }
+      IpcMessage::SentToWindow(msg) if msg.event == PUBLISH_EVENT => match serde_json::from_value::<PublishMessage>(msg.content) {
+        Ok(message) => broker.publish(message),
+        Err(e) => println!("invalid deno publish:{:?}", e),
+      },
+      IpcMessage::SentToWindow(msg) if msg.event == SUBSCRIBE_EVENT => match serde_json::from_value::<WorkerSubscription>(msg.content) {
+        Ok(subscription) => {
+          //订阅者只能是发出订阅的 worker 本身
+          if workers_table.contains(&source) {
+            broker.subscribe(subscription.id, subscription.pattern, Subscriber::Worker(source.clone()));
           }
         }
+        Err(e) => println!("invalid deno subscribe:{:?}", e),
+      },
+      IpcMessage::SentToWindow(msg) if msg.event == UNSUBSCRIBE_EVENT => {
+        if let Some(id) = msg.content.get("id").and_then(|id| id.as_str()) {
+          broker.unsubscribe(id);
+        }
       }
-      IpcMessage::SentToDeno(msg) => {
-        let events_manager_map = workers_table_ref.read().await;
-        match events_manager_map.get(&msg.id) {
-          Some(worker_manager) => {
-            //通知指定的worker
-            worker_manager.events_manager.send(msg.event.clone(), msg.content).await.unwrap();
-          }
-          None => {
-            //通知所有的worker
-            for (_key, worker_manager) in events_manager_map.iter() {
-              worker_manager.events_manager.send(msg.event.clone(), msg.content.clone()).await.unwrap();
-            }
+      IpcMessage::SentToWindow(msg) if msg.event == HOST_CALL_EVENT => match serde_json::from_value::<HostCall>(msg.content) {
+        Ok(call) => {
+          //命令可能耗时 在独立任务中执行
+          let commands = commands.clone();
+          let fanout = fanout.clone();
+          let workers_table = workers_table.clone();
+          let source = source.clone();
+          tokio::task::spawn(async move {
+            let reply = commands.call(call).await;
+            let content = serde_json::to_value(reply).unwrap_or_default();
+            fanout.send(&workers_table.snapshot(), &source, HOST_REPLY_EVENT.to_string(), content);
+          });
+        }
+        Err(e) => println!("invalid deno host call:{:?}", e),
+      },
+      IpcMessage::SentToWindow(msg) if msg.event == WINDOW_EVENT => match serde_json::from_value::<WindowRequest>(msg.content) {
+        Ok(request) => {
+          //创建窗口需要等待主线程 在独立任务中执行
+          let windows = windows.clone();
+          let fanout = fanout.clone();
+          let workers_table = workers_table.clone();
+          let handle_ref = handle_ref.clone();
+          let source = source.clone();
+          let notifier = notifier.clone();
+          tokio::task::spawn(async move {
+            let reply = windows.handle(&handle_ref, &source, request);
+            //显示 隐藏 关闭窗口不产生窗口事件 在此重新计算可见状态
+            notifier.refresh_visibility();
+            let content = serde_json::to_value(reply).unwrap_or_default();
+            fanout.send(&workers_table.snapshot(), &source, WINDOW_REPLY_EVENT.to_string(), content);
+          });
+        }
+        Err(e) => println!("invalid deno window request:{:?}", e),
+      },
+      IpcMessage::SentToWindow(msg) if msg.event == KV_EVENT => match serde_json::from_value::<KvRequest>(msg.content) {
+        Ok(request) => {
+          //读写 sqlite 在独立任务中执行 不阻塞路由
+          let fanout = fanout.clone();
+          let workers_table = workers_table.clone();
+          let source = source.clone();
+          let notifier = notifier.clone();
+          tokio::task::spawn(async move {
+            let reply = notifier.kv_handle(&source, request).await;
+            let content = serde_json::to_value(reply).unwrap_or_default();
+            fanout.send(&workers_table.snapshot(), &source, KV_REPLY_EVENT.to_string(), content);
+          });
+        }
+        Err(e) => println!("invalid deno kv request:{:?}", e),
+      },
+      IpcMessage::SentToWindow(msg) if msg.event == EMIT_EVENT => match serde_json::from_value::<EmitMessage>(msg.content) {
+        Ok(EmitMessage { event, payload, target }) => {
+          //不符合 schema 的消息不转发到窗口
+          let result = schemas.validate(&event, Direction::ToWindow, &payload).and_then(|_| target::emit(&handle_ref, &channels, &source, &target, &event, payload));
+          if let Err(e) = result {
+            println!("deno emit {} failed: {}", event, e);
+            let content = serde_json::json!({ "event": event, "target": target, "error": e });
+            fanout.send(&workers_table.snapshot(), &source, EMIT_ERROR_EVENT.to_string(), content);
           }
         }
+        Err(e) => println!("invalid deno emit:{:?}", e),
+      },
+      IpcMessage::SentToWindow(msg) => {
+        //不符合 schema 的消息不转发到窗口
+        if let Err(e) = schemas.validate(&msg.event, Direction::ToWindow, &msg.content) {
+          println!("rejected deno message: {}", e);
+          continue;
+        }
+        //key 为窗口 label, 为空时广播
+        if let Err(e) = target::emit(&handle_ref, &channels, &source, &WindowTarget::from_key(&msg.id), &msg.event, msg.content) {
+          println!("deno message {} to `{}` dropped: {}", msg.event, msg.id, e);
+        }
+      }
+      IpcMessage::SentToDeno(msg) => {
+        //通知指定的worker 找不到时通知所有的worker
+        fanout.dispatch(&workers_table.snapshot(), &msg.id, msg.event, msg.content);
       }
     }
   }
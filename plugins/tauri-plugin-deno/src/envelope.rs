@@ -0,0 +1,56 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{ResourceId, Runtime};
+
+/// webview 发往 deno 的消息外层 {"$sender": MessageSender, "$content": 原始内容}
+/// 只有以 with_sender 创建的通道(或广播时传入 with_sender)才会包装, 由 worker-js 的 listen 拆开
+pub const SENDER_KEY: &str = "$sender";
+pub const CONTENT_KEY: &str = "$content";
+
+/// 消息来源 webview 为发送消息的 webview, window 为其所在窗口; rid 为发送所用的通道, 广播消息为空; timestamp 为毫秒时间戳
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageSender {
+  pub webview: String,
+  pub window: String,
+  pub rid: Option<ResourceId>,
+  pub origin: Option<String>,
+  pub timestamp: u64,
+}
+
+impl MessageSender {
+  pub fn from_webview<R: Runtime>(webview: &tauri::Webview<R>, rid: Option<ResourceId>) -> Self {
+    Self {
+      webview: webview.label().to_string(),
+      window: webview.window().label().to_string(),
+      rid,
+      origin: webview.url().ok().map(|url| url.to_string()),
+      timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default(),
+    }
+  }
+
+  /// 包装为发往 deno 的消息 未开启 with_sender 时原样返回
+  pub fn wrap_if<R: Runtime>(with_sender: bool, webview: &tauri::Webview<R>, rid: Option<ResourceId>, content: serde_json::Value) -> serde_json::Value {
+    if !with_sender {
+      return content;
+    }
+    Self::from_webview(webview, rid).wrap(content)
+  }
+
+  /// 包装为发往 deno 的消息
+  pub fn wrap(&self, content: serde_json::Value) -> serde_json::Value {
+    let mut envelope = serde_json::Map::new();
+    envelope.insert(SENDER_KEY.to_string(), serde_json::to_value(self).unwrap_or_default());
+    envelope.insert(CONTENT_KEY.to_string(), content);
+    serde_json::Value::Object(envelope)
+  }
+}
+
+/// 取出外层中的原始内容 不是外层时原样返回
+pub fn unwrap(value: serde_json::Value) -> serde_json::Value {
+  match value {
+    serde_json::Value::Object(mut envelope) if envelope.contains_key(SENDER_KEY) && envelope.contains_key(CONTENT_KEY) => envelope.remove(CONTENT_KEY).unwrap_or_default(),
+    value => value,
+  }
+}
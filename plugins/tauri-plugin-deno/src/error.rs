@@ -1,4 +1,6 @@
-use serde::{ser::Serializer, Serialize};
+use serde::{ser::SerializeStruct, ser::Serializer, Serialize};
+use serde_json::json;
+use tauri::ResourceId;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -6,16 +8,93 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
   #[error(transparent)]
   Io(#[from] std::io::Error),
+  #[error(transparent)]
+  Tauri(#[from] tauri::Error),
+  #[error(transparent)]
+  Json(#[from] serde_json::Error),
+  #[error("invalid binary message: {0}")]
+  InvalidBinary(String),
+  #[error("invalid json schema for event `{event}`: {message}")]
+  InvalidSchema { event: String, message: String },
+  #[error("payload of event `{event}` does not match its schema: {message}")]
+  InvalidPayload { event: String, message: String },
+  #[error("permission denied: {0}")]
+  PermissionDenied(String),
+  #[error("deno worker `{0}` is not running")]
+  WorkerNotFound(String),
+  #[error("event target not found: {0}")]
+  TargetNotFound(String),
+  #[error("deno channel {0} does not exist")]
+  ChannelNotFound(ResourceId),
+  #[error("deno channel {0} is closed")]
+  ChannelClosed(ResourceId),
+  #[error("deno worker `{worker}` did not reply within {millis}ms")]
+  Timeout { worker: String, millis: u64 },
+  #[error("deno stream `{0}` does not exist or has ended")]
+  StreamNotFound(String),
+  #[error("topic subscription `{0}` does not exist")]
+  SubscriptionNotFound(String),
+  #[error("ipc queue is full, rejected message from `{0}`")]
+  QueueFull(String),
+  #[error("{0}")]
+  Handler(String),
+  #[error("kv store error: {0}")]
+  Kv(#[from] rusqlite::Error),
   #[cfg(mobile)]
   #[error(transparent)]
   PluginInvoke(#[from] tauri::plugin::mobile::PluginInvokeError),
 }
 
+impl Error {
+  /// 供前端区分错误类型的代码
+  pub fn code(&self) -> &'static str {
+    match self {
+      Error::Io(_) => "io",
+      Error::Tauri(_) => "tauri",
+      Error::Json(_) => "json",
+      Error::InvalidBinary(_) => "invalidBinary",
+      Error::InvalidSchema { .. } => "invalidSchema",
+      Error::InvalidPayload { .. } => "invalidPayload",
+      Error::PermissionDenied(_) => "permissionDenied",
+      Error::WorkerNotFound(_) => "workerNotFound",
+      Error::TargetNotFound(_) => "targetNotFound",
+      Error::ChannelNotFound(_) => "channelNotFound",
+      Error::ChannelClosed(_) => "channelClosed",
+      Error::Timeout { .. } => "timeout",
+      Error::StreamNotFound(_) => "streamNotFound",
+      Error::SubscriptionNotFound(_) => "subscriptionNotFound",
+      Error::QueueFull(_) => "queueFull",
+      Error::Handler(_) => "handlerError",
+      Error::Kv(_) => "kv",
+      #[cfg(mobile)]
+      Error::PluginInvoke(_) => "pluginInvoke",
+    }
+  }
+
+  /// 错误相关的结构化数据
+  pub fn details(&self) -> serde_json::Value {
+    match self {
+      Error::InvalidSchema { event, .. } | Error::InvalidPayload { event, .. } => json!({ "event": event }),
+      Error::WorkerNotFound(worker) => json!({ "worker": worker }),
+      Error::StreamNotFound(id) | Error::SubscriptionNotFound(id) => json!({ "id": id }),
+      Error::QueueFull(source) => json!({ "source": source }),
+      Error::ChannelNotFound(rid) | Error::ChannelClosed(rid) => json!({ "rid": rid }),
+      Error::Timeout { worker, millis } => json!({ "worker": worker, "timeout": millis }),
+      _ => serde_json::Value::Null,
+    }
+  }
+}
+
+/// 序列化为 {code, message, details}
 impl Serialize for Error {
   fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
   where
     S: Serializer,
   {
-    serializer.serialize_str(self.to_string().as_ref())
+    let mut state = serializer.serialize_struct("Error", 3)?;
+    state.serialize_field("code", self.code())?;
+    state.serialize_field("message", &self.to_string())?;
+    state.serialize_field("details", &self.details())?;
+    state.end()
   }
 }
@@ -0,0 +1,56 @@
+use std::{borrow::Cow, sync::Arc};
+
+use deno_lib::deno_runtime::deno_core::{Extension, ExtensionFileSource, OpDecl};
+
+/// Extension 不能跨线程复制 每个 worker 启动时由工厂重新创建
+pub type ExtensionFactory = Arc<dyn Fn() -> Extension + Send + Sync>;
+
+/// 宿主注册的 deno_core 扩展 创建 CliMainWorker 时安装
+/// 开发模式下文件变更重启 worker 时同样重新安装
+#[derive(Clone, Default)]
+pub struct WorkerExtensions {
+  factories: Arc<Vec<ExtensionFactory>>,
+}
+
+impl WorkerExtensions {
+  pub fn new(factories: Vec<ExtensionFactory>) -> Self {
+    Self { factories: Arc::new(factories) }
+  }
+
+  pub fn build(&self) -> Vec<Extension> {
+    self.factories.iter().map(|factory| factory()).collect()
+  }
+
+  /// 只包含 op 的扩展 脚本中以 worker-js 的 hostOps(<name>).<op 名> 调用
+  /// deno 2 不再向脚本暴露 Deno.core, 由扩展的入口模块从 ext:core/mod.js 取出这些 op 挂到 OPS_GLOBAL 下
+  pub fn from_ops(name: &'static str, ops: Vec<OpDecl>) -> ExtensionFactory {
+    //specifier 需要 'static 只在注册时生成一次
+    let specifier: &'static str = Box::leak(format!("ext:tauri-plugin-deno/{}.js", name).into_boxed_str());
+    let code: Arc<str> = entry_module(name, &ops).into();
+    Arc::new(move || Extension {
+      name,
+      ops: Cow::Owned(ops.clone()),
+      esm_files: Cow::Owned(vec![ExtensionFileSource::new_computed(specifier, code.clone())]),
+      esm_entry_point: Some(specifier),
+      ..Default::default()
+    })
+  }
+}
+
+/// 脚本取得 op 的全局键 与 worker-js 的 hostOps 一致
+const OPS_GLOBAL: &str = "tauri-plugin-deno.ops";
+
+/// 扩展的入口模块 只暴露本扩展注册的 op
+fn entry_module(name: &str, ops: &[OpDecl]) -> String {
+  let names = serde_json::to_string(&ops.iter().map(|op| op.name).collect::<Vec<_>>()).unwrap_or_default();
+  let name = serde_json::to_string(name).unwrap_or_default();
+  format!(
+    r#"import {{ core }} from "ext:core/mod.js";
+const key = Symbol.for("{OPS_GLOBAL}");
+const registry = globalThis[key] ?? (globalThis[key] = {{}});
+const ops = {{}};
+for (const op of {names}) ops[op] = core.ops[op];
+registry[{name}] = Object.freeze(ops);
+"#
+  )
+}
@@ -0,0 +1,336 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+  },
+  time::{Duration, Instant},
+};
+
+use deno_lib::deno_ipc::events_manager::EventsManager;
+use futures::{future::BoxFuture, FutureExt};
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::{host::HOST_REPLY_EVENT, kv::KV_REPLY_EVENT, queue::OverflowPolicy, registry::WorkersSnapshot, window::WINDOW_REPLY_EVENT};
+
+/// worker 正在等待的应答 不受容量限制, 满载时也不会被丢弃
+const REPLY_EVENTS: [&str; 3] = [HOST_REPLY_EVENT, KV_REPLY_EVENT, WINDOW_REPLY_EVENT];
+
+/// 同一 worker 的丢弃/超时/失败日志最短间隔, 间隔内的只计数, 下次输出时一并报告
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 投递配置
+/// timeout 单个 worker 单条消息的投递超时
+/// capacity 每个 worker 待投递消息的上限
+/// policy 超出上限时的处理 DropOldest 丢弃最旧的消息, 其余策略丢弃新消息 (路由不能为单个 worker 阻塞)
+#[derive(Debug, Clone, Copy)]
+pub struct FanoutConfig {
+  pub timeout: Duration,
+  pub capacity: usize,
+  pub policy: OverflowPolicy,
+}
+
+impl Default for FanoutConfig {
+  fn default() -> Self {
+    Self {
+      timeout: Duration::from_secs(5),
+      capacity: 256,
+      policy: OverflowPolicy::DropNewest,
+    }
+  }
+}
+
+/// 单个 worker 的投递计数
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetStats {
+  pub delivered: u64,
+  pub failed: u64,
+  pub timed_out: u64,
+  pub dropped: u64,
+  pub last_error: Option<String>,
+}
+
+/// 路由发往 deno 的投递状态
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryStats {
+  pub delivered: u64,
+  pub failed: u64,
+  pub timed_out: u64,
+  pub dropped: u64,
+  pub targets: HashMap<String, TargetStats>,
+}
+
+enum Outcome {
+  Delivered,
+  Failed(String),
+  TimedOut,
+  Dropped,
+}
+
+/// deliver 为尚未开始的投递, 由投递任务按顺序执行
+struct Envelope {
+  event: String,
+  deliver: BoxFuture<'static, Result<(), String>>,
+}
+
+impl Envelope {
+  fn is_reply(&self) -> bool {
+    REPLY_EVENTS.contains(&self.event.as_str())
+  }
+}
+
+/// 单个 worker 的日志限流状态
+#[derive(Default)]
+struct Warning {
+  last: Option<Instant>,
+  suppressed: u64,
+}
+
+/// 单个 worker 的投递通道 由投递任务按顺序取出
+#[derive(Default)]
+struct Lane {
+  buffer: Mutex<VecDeque<Envelope>>,
+  readable: Notify,
+  closed: AtomicBool,
+}
+
+impl Lane {
+  fn close(&self) {
+    self.closed.store(true, Ordering::SeqCst);
+    self.readable.notify_one();
+  }
+}
+
+/// 路由到 deno 的投递
+/// 每个 worker 一条独立的投递通道, 路由只负责入队不等待;
+/// 慢的 worker 只积压自己的通道, 同一 worker 的消息保持顺序, 超时和失败计入统计
+#[derive(Clone)]
+pub struct Fanout {
+  config: FanoutConfig,
+  lanes: Arc<Mutex<HashMap<String, Arc<Lane>>>>,
+  stats: Arc<Mutex<DeliveryStats>>,
+  warnings: Arc<Mutex<HashMap<String, Warning>>>,
+}
+
+impl Fanout {
+  pub fn new(config: FanoutConfig) -> Self {
+    Self {
+      config,
+      lanes: Default::default(),
+      stats: Default::default(),
+      warnings: Default::default(),
+    }
+  }
+
+  /// 投递到指定 worker, target 不存在时投递到所有 worker
+  pub fn dispatch(&self, workers: &WorkersSnapshot, target: &str, event: String, content: serde_json::Value) {
+    match workers.get(target) {
+      Some(worker_manager) => self.enqueue(target, worker_manager.events_manager.clone(), event, content),
+      None => {
+        for (key, worker_manager) in workers.iter() {
+          self.enqueue(key, worker_manager.events_manager.clone(), event.clone(), content.clone());
+        }
+      }
+    }
+    self.prune(workers);
+  }
+
+  /// 只投递到指定 worker 不存在时返回 false, 不会广播
+  pub fn send(&self, workers: &WorkersSnapshot, target: &str, event: String, content: serde_json::Value) -> bool {
+    let Some(worker_manager) = workers.get(target) else {
+      return false;
+    };
+    self.enqueue(target, worker_manager.events_manager.clone(), event, content);
+    true
+  }
+
+  pub fn stats(&self) -> DeliveryStats {
+    self.stats.lock().unwrap().clone()
+  }
+
+  fn enqueue(&self, key: &str, events_manager: EventsManager, event: String, content: serde_json::Value) {
+    let deliver = {
+      let event = event.clone();
+      async move { events_manager.send(event, content).await.map(|_| ()).map_err(|e| format!("{:?}", e)) }.boxed()
+    };
+    self.enqueue_envelope(key, Envelope { event, deliver });
+  }
+
+  fn enqueue_envelope(&self, key: &str, envelope: Envelope) {
+    let lane = self.lanes.lock().unwrap().entry(key.to_string()).or_insert_with(|| self.spawn_lane(key.to_string())).clone();
+    let mut buffer = lane.buffer.lock().unwrap();
+    if buffer.len() >= self.config.capacity && !envelope.is_reply() {
+      //应答不计入丢弃的候选 全部是应答时丢弃新消息
+      let oldest = match self.config.policy {
+        OverflowPolicy::DropOldest => buffer.iter().position(|queued| !queued.is_reply()),
+        _ => None,
+      };
+      self.record(key, Outcome::Dropped);
+      match oldest {
+        Some(index) => {
+          buffer.remove(index);
+        }
+        None => return,
+      }
+    }
+    buffer.push_back(envelope);
+    drop(buffer);
+    lane.readable.notify_one();
+  }
+
+  fn spawn_lane(&self, key: String) -> Arc<Lane> {
+    let lane = Arc::new(Lane::default());
+    let lane_ref = lane.clone();
+    let fanout = self.clone();
+    tokio::task::spawn(async move {
+      loop {
+        let next = lane_ref.buffer.lock().unwrap().pop_front();
+        let Some(Envelope { deliver, .. }) = next else {
+          if lane_ref.closed.load(Ordering::SeqCst) {
+            break;
+          }
+          lane_ref.readable.notified().await;
+          continue;
+        };
+        let outcome = match tokio::time::timeout(fanout.config.timeout, deliver).await {
+          Ok(Ok(())) => Outcome::Delivered,
+          Ok(Err(e)) => Outcome::Failed(format!("{:?}", e)),
+          Err(_) => Outcome::TimedOut,
+        };
+        fanout.record(&key, outcome);
+      }
+    });
+    lane
+  }
+
+  /// 移除已注销 worker 的投递通道 通道中剩余的消息投递完后任务退出
+  fn prune(&self, workers: &WorkersSnapshot) {
+    let mut lanes = self.lanes.lock().unwrap();
+    if lanes.len() > workers.len() {
+      lanes.retain(|key, lane| {
+        let alive = workers.contains_key(key);
+        if !alive {
+          lane.close();
+        }
+        alive
+      });
+    }
+  }
+
+  fn record(&self, key: &str, outcome: Outcome) {
+    let mut stats = self.stats.lock().unwrap();
+    let DeliveryStats {
+      delivered,
+      failed,
+      timed_out,
+      dropped,
+      targets,
+    } = &mut *stats;
+    let target = targets.entry(key.to_string()).or_default();
+    match outcome {
+      Outcome::Delivered => {
+        *delivered += 1;
+        target.delivered += 1;
+      }
+      Outcome::Failed(error) => {
+        *failed += 1;
+        target.failed += 1;
+        self.warn(key, format!("failed to deliver message to deno worker {}: {}", key, error));
+        target.last_error = Some(error);
+      }
+      Outcome::TimedOut => {
+        *timed_out += 1;
+        target.timed_out += 1;
+        self.warn(key, format!("delivering message to deno worker {} timed out", key));
+        target.last_error = Some(format!("timed out after {}ms", self.config.timeout.as_millis()));
+      }
+      Outcome::Dropped => {
+        *dropped += 1;
+        target.dropped += 1;
+        self.warn(key, format!("deno worker {} is not keeping up, dropped a message", key));
+      }
+    }
+  }
+
+  /// 按 worker 限流输出日志 完整的计数见 deno_delivery_stats
+  fn warn(&self, key: &str, message: String) {
+    let mut warnings = self.warnings.lock().unwrap();
+    let warning = warnings.entry(key.to_string()).or_default();
+    if warning.last.is_some_and(|last| last.elapsed() < LOG_INTERVAL) {
+      warning.suppressed += 1;
+      return;
+    }
+    match warning.suppressed {
+      0 => println!("{}", message),
+      n => println!("{} ({} similar messages suppressed)", message, n),
+    }
+    warning.last = Some(Instant::now());
+    warning.suppressed = 0;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tokio::sync::oneshot;
+
+  fn envelope(deliver: impl std::future::Future<Output = ()> + Send + 'static) -> Envelope {
+    Envelope {
+      event: "test".to_string(),
+      deliver: async move {
+        deliver.await;
+        Ok(())
+      }
+      .boxed(),
+    }
+  }
+
+  #[tokio::test]
+  async fn slow_lane_does_not_block_other_lanes() {
+    let fanout = Fanout::new(FanoutConfig::default());
+    let (_release, blocked) = oneshot::channel::<()>();
+    fanout.enqueue_envelope(
+      "slow",
+      envelope(async move {
+        let _ = blocked.await;
+      }),
+    );
+    let (done, delivered) = oneshot::channel();
+    fanout.enqueue_envelope(
+      "fast",
+      envelope(async move {
+        let _ = done.send(());
+      }),
+    );
+    tokio::time::timeout(Duration::from_secs(1), delivered).await.expect("fast lane was held up").unwrap();
+    let stats = fanout.stats();
+    assert_eq!(stats.targets["fast"].delivered, 1);
+    assert!(!stats.targets.contains_key("slow"));
+  }
+
+  #[tokio::test]
+  async fn full_lane_drops_and_counts() {
+    let fanout = Fanout::new(FanoutConfig { capacity: 1, ..Default::default() });
+    let (_release, blocked) = oneshot::channel::<()>();
+    let (started, running) = oneshot::channel();
+    fanout.enqueue_envelope(
+      "slow",
+      envelope(async move {
+        let _ = started.send(());
+        let _ = blocked.await;
+      }),
+    );
+    //等第一条消息开始投递, 通道内不再有积压
+    running.await.unwrap();
+    for _ in 0..3 {
+      fanout.enqueue_envelope("slow", envelope(async {}));
+    }
+    let stats = fanout.stats();
+    assert_eq!(stats.dropped, 2);
+    assert_eq!(stats.targets["slow"].dropped, 2);
+    assert_eq!(fanout.warnings.lock().unwrap()["slow"].suppressed, 1);
+  }
+}
@@ -0,0 +1,198 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use deno_lib::deno_ipc::events_manager::EventsManager;
+use serde::{Deserialize, Serialize};
+use tauri::http::{Request, Response, StatusCode};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{binary::BinaryPayload, scope::glob_match};
+
+/// 自定义协议名 地址为 deno://localhost/<worker>/<path>
+/// windows 和 android 上 webview 以 http://deno.localhost/<worker>/<path> 访问, 因此 worker 取路径第一段
+pub const FETCH_SCHEME: &str = "deno";
+/// 插件 -> deno 的请求事件 {id, method, url, headers, body}
+pub const FETCH_EVENT: &str = "deno:fetch";
+/// deno -> 插件 的响应分片 {id, kind, ...} 由路由拦截
+pub const FETCH_RESPONSE_EVENT: &str = "deno:fetch-response";
+/// 默认的分片间隔超时 每收到一个分片重新计时
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 授权 webview 访问 worker 的 serve 处理函数 均为 label 通配
+#[derive(Debug, Clone)]
+pub struct FetchGrant {
+  pub webview: String,
+  pub worker: String,
+}
+
+/// 协议请求配置 未授权的 webview 一律返回 403
+#[derive(Debug, Clone)]
+pub struct FetchConfig {
+  pub timeout: Duration,
+  pub grants: Vec<FetchGrant>,
+}
+
+impl Default for FetchConfig {
+  fn default() -> Self {
+    Self { timeout: FETCH_TIMEOUT, grants: Vec::new() }
+  }
+}
+
+impl FetchConfig {
+  pub fn allowed(&self, webview: &str, worker: &str) -> bool {
+    self.grants.iter().any(|grant| glob_match(&grant.webview, webview) && glob_match(&grant.worker, worker))
+  }
+}
+
+/// 发给 worker 的请求 url 已去掉 worker 段, body 为 $binary 格式
+#[derive(Serialize, Debug, Clone)]
+pub struct FetchRequest {
+  pub id: String,
+  pub method: String,
+  pub url: String,
+  pub headers: Vec<(String, String)>,
+  pub body: Option<BinaryPayload>,
+}
+
+/// worker 发来的响应分片 head 之后为若干 chunk, 以 end 或 error 结束
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum FetchFrame {
+  Head {
+    status: u16,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+  },
+  Chunk {
+    data: BinaryPayload,
+  },
+  End,
+  Error {
+    message: String,
+  },
+}
+
+#[derive(Deserialize, Debug)]
+struct FetchEnvelope {
+  id: String,
+  #[serde(flatten)]
+  frame: FetchFrame,
+}
+
+/// 自定义协议请求管理器
+/// pending 以请求 id 保存分片的接收端, 路由按到达顺序写入
+/// tauri 的协议响应不支持流式写出, 分片在此合并后一次性返回给 webview
+/// 因此不适合无结束的响应(如日志追踪), 这类数据应改用 stream_deno
+#[derive(Clone, Default)]
+pub struct FetchManager {
+  pending: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<FetchFrame>>>>,
+  config: Arc<FetchConfig>,
+}
+
+impl FetchManager {
+  pub fn new(config: FetchConfig) -> Self {
+    Self {
+      pending: Default::default(),
+      config: Arc::new(config),
+    }
+  }
+
+  /// 从请求路径中取出 worker 及去掉 worker 段后的 url
+  pub fn split_worker(request: &Request<Vec<u8>>) -> Option<(String, String)> {
+    let uri = request.uri();
+    let path = uri.path().trim_start_matches('/');
+    let (worker, rest) = path.split_once('/').unwrap_or((path, ""));
+    if worker.is_empty() {
+      return None;
+    }
+    let query = uri.query().map(|query| format!("?{}", query)).unwrap_or_default();
+    Some((worker.to_string(), format!("{}://localhost/{}{}", FETCH_SCHEME, rest, query)))
+  }
+
+  /// 把请求交给 worker 的 serve 处理函数并等待完整响应 出错时转为对应状态码的响应
+  /// webview 为发起请求的 webview label, 须经 fetch_permission 授权
+  pub async fn fetch(&self, webview: &str, events_manager: Option<EventsManager>, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let Some((worker, url)) = Self::split_worker(&request) else {
+      return error_response(StatusCode::NOT_FOUND, "missing worker in url".to_string());
+    };
+    if !self.config.allowed(webview, &worker) {
+      return error_response(StatusCode::FORBIDDEN, format!("webview `{}` is not allowed to fetch worker `{}`", webview, worker));
+    }
+    let Some(events_manager) = events_manager else {
+      return error_response(StatusCode::NOT_FOUND, format!("worker not found: {}", worker));
+    };
+    let id = Uuid::new_v4().to_string();
+    let headers = request.headers().iter().filter_map(|(name, value)| value.to_str().ok().map(|value| (name.to_string(), value.to_string()))).collect();
+    let method = request.method().to_string();
+    let body = request.into_body();
+    let content = FetchRequest {
+      id: id.clone(),
+      method,
+      url,
+      headers,
+      body: if body.is_empty() { None } else { Some(BinaryPayload::new(body)) },
+    };
+    let (sender, receiver) = mpsc::unbounded_channel();
+    self.pending.lock().unwrap().insert(id.clone(), sender);
+    if events_manager.send(FETCH_EVENT.to_string(), serde_json::to_value(content).unwrap_or_default()).await.is_err() {
+      self.pending.lock().unwrap().remove(&id);
+      return error_response(StatusCode::NOT_FOUND, format!("worker not found: {}", worker));
+    }
+    let response = collect(receiver, self.config.timeout)
+      .await
+      .unwrap_or_else(|| error_response(StatusCode::GATEWAY_TIMEOUT, format!("worker `{}` sent nothing for {}ms", worker, self.config.timeout.as_millis())));
+    self.pending.lock().unwrap().remove(&id);
+    response
+  }
+
+  /// 路由收到分片后转交给等待中的请求 结束后移除
+  pub fn dispatch(&self, content: serde_json::Value) {
+    let FetchEnvelope { id, frame } = match serde_json::from_value(content) {
+      Ok(envelope) => envelope,
+      Err(e) => {
+        println!("invalid deno fetch frame:{:?}", e);
+        return;
+      }
+    };
+    let mut pending = self.pending.lock().unwrap();
+    let finished = matches!(frame, FetchFrame::End | FetchFrame::Error { .. });
+    let delivered = pending.get(&id).map(|sender| sender.send(frame).is_ok()).unwrap_or_default();
+    if finished || !delivered {
+      pending.remove(&id);
+    }
+  }
+}
+
+/// 按顺序合并分片 未收到 head 就结束视为 worker 出错
+/// 两个分片之间超过 timeout 返回 None, 持续输出的长响应不会因总时长超时
+async fn collect(mut receiver: mpsc::UnboundedReceiver<FetchFrame>, timeout: Duration) -> Option<Response<Vec<u8>>> {
+  let mut head: Option<(u16, Vec<(String, String)>)> = None;
+  let mut body = Vec::new();
+  while let Some(frame) = tokio::time::timeout(timeout, receiver.recv()).await.ok()? {
+    match frame {
+      FetchFrame::Head { status, headers } => head = Some((status, headers)),
+      FetchFrame::Chunk { data } => body.extend(data.data),
+      FetchFrame::End => break,
+      FetchFrame::Error { message } => return Some(error_response(StatusCode::INTERNAL_SERVER_ERROR, message)),
+    }
+  }
+  let Some((status, headers)) = head else {
+    return Some(error_response(StatusCode::BAD_GATEWAY, "worker ended the response without a head".to_string()));
+  };
+  let mut builder = Response::builder().status(status);
+  for (name, value) in headers {
+    builder = builder.header(name, value);
+  }
+  //跨域头由 worker 的处理函数自行设置 插件不默认放开
+  Some(builder.body(body).unwrap_or_else(|e| error_response(StatusCode::BAD_GATEWAY, format!("invalid response from worker: {}", e))))
+}
+
+fn error_response(status: StatusCode, message: String) -> Response<Vec<u8>> {
+  let mut response = Response::new(message.into_bytes());
+  *response.status_mut() = status;
+  response
+}
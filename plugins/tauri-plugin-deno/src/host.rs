@@ -0,0 +1,76 @@
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// deno -> 插件 调用宿主注册的 rust 命令 {id, name, args}
+pub const HOST_CALL_EVENT: &str = "deno:host-call";
+/// 插件 -> deno 命令结果 {id, result, error}
+pub const HOST_REPLY_EVENT: &str = "deno:host-reply";
+
+pub type HostFuture = Pin<Box<dyn Future<Output = Result<serde_json::Value, String>> + Send>>;
+pub type HostHandler = Arc<dyn Fn(serde_json::Value) -> HostFuture + Send + Sync>;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HostCall {
+  pub id: String,
+  pub name: String,
+  #[serde(default)]
+  pub args: serde_json::Value,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HostReply {
+  pub id: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub result: Option<serde_json::Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+/// 宿主命令表 由 Builder::command 注册, worker 通过 callHost 调用
+#[derive(Clone, Default)]
+pub struct HostCommands {
+  handlers: Arc<HashMap<String, HostHandler>>,
+}
+
+impl HostCommands {
+  pub fn new(handlers: HashMap<String, HostHandler>) -> Self {
+    Self { handlers: Arc::new(handlers) }
+  }
+
+  /// 包装为按 json 收发的处理函数 参数反序列化失败时返回错误
+  pub fn handler<Args, Resp, E, Fut, F>(f: F) -> HostHandler
+  where
+    Args: DeserializeOwned + Send + 'static,
+    Resp: Serialize + Send + 'static,
+    E: std::fmt::Display + Send + 'static,
+    Fut: Future<Output = Result<Resp, E>> + Send + 'static,
+    F: Fn(Args) -> Fut + Send + Sync + 'static,
+  {
+    let f = Arc::new(f);
+    Arc::new(move |args| {
+      let f = f.clone();
+      Box::pin(async move {
+        let args: Args = serde_json::from_value(args).map_err(|e| format!("invalid arguments: {}", e))?;
+        let resp = f(args).await.map_err(|e| e.to_string())?;
+        serde_json::to_value(resp).map_err(|e| e.to_string())
+      })
+    })
+  }
+
+  /// 执行命令 处理函数 panic 时返回错误, 不影响路由
+  pub async fn call(&self, call: HostCall) -> HostReply {
+    let HostCall { id, name, args } = call;
+    let result = match self.handlers.get(&name) {
+      Some(handler) => match tokio::task::spawn(handler(args)).await {
+        Ok(result) => result,
+        Err(e) => Err(format!("command `{}` panicked: {}", name, e)),
+      },
+      None => Err(format!("command not found: {}", name)),
+    };
+    match result {
+      Ok(result) => HostReply { id, result: Some(result), error: None },
+      Err(error) => HostReply { id, result: None, error: Some(error) },
+    }
+  }
+}
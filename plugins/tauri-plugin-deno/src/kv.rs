@@ -0,0 +1,143 @@
+use std::{
+  path::Path,
+  sync::{Arc, Mutex},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+/// deno -> 插件 kv 操作请求 {id, op, ...}
+pub const KV_EVENT: &str = "deno:kv";
+/// 插件 -> deno kv 操作结果 {id, result, error}
+pub const KV_REPLY_EVENT: &str = "deno:kv-reply";
+/// 插件 -> webview 和 deno 的变更通知 KvChange
+pub const KV_CHANGE_EVENT: &str = "deno:kv-change";
+/// 未指定 kv_path 时 在应用数据目录下的文件名
+pub const KV_FILE: &str = "deno_kv.sqlite";
+
+/// 变更通知 deleted 为 true 表示删除, 此时 value 为空; 写入 null 时 deleted 为 false
+/// source 为发起修改的窗口 label 或 worker key
+#[derive(Serialize, Debug, Clone)]
+pub struct KvChange {
+  pub key: String,
+  pub value: Option<serde_json::Value>,
+  pub deleted: bool,
+  pub source: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct KvEntry {
+  pub key: String,
+  pub value: serde_json::Value,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum KvOp {
+  Get { key: String },
+  Set { key: String, value: serde_json::Value },
+  Delete { key: String },
+  List { prefix: Option<String> },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct KvRequest {
+  pub id: String,
+  #[serde(flatten)]
+  pub op: KvOp,
+}
+
+#[derive(Serialize, Debug)]
+pub struct KvReply {
+  pub id: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub result: Option<serde_json::Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<crate::Error>,
+}
+
+/// sqlite 键值存储 值以 json 文本保存, 每次写入在独立事务中完成
+/// 连接只有一个 读写在阻塞线程池中串行执行
+/// 写入的 on_commit 在提交后 释放连接前调用, 变更通知的顺序与提交顺序一致
+#[derive(Clone)]
+pub struct KvStore {
+  conn: Arc<Mutex<Connection>>,
+}
+
+impl KvStore {
+  pub fn open(path: &Path) -> crate::Result<Self> {
+    if let Some(dir) = path.parent() {
+      std::fs::create_dir_all(dir)?;
+    }
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.execute("CREATE TABLE IF NOT EXISTS deno_kv (key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL, updated_at INTEGER NOT NULL)", [])?;
+    Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+  }
+
+  async fn with_conn<T: Send + 'static>(&self, f: impl FnOnce(&mut Connection) -> crate::Result<T> + Send + 'static) -> crate::Result<T> {
+    let conn = self.conn.clone();
+    tokio::task::spawn_blocking(move || f(&mut conn.lock().unwrap_or_else(std::sync::PoisonError::into_inner)))
+      .await
+      .map_err(|e| crate::Error::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))?
+  }
+
+  pub async fn get(&self, key: String) -> crate::Result<Option<serde_json::Value>> {
+    self
+      .with_conn(move |conn| {
+        let value: Option<String> = conn.query_row("SELECT value FROM deno_kv WHERE key = ?1", params![key], |row| row.get(0)).optional()?;
+        Ok(value.map(|value| serde_json::from_str(&value)).transpose()?)
+      })
+      .await
+  }
+
+  pub async fn set(&self, key: String, value: serde_json::Value, on_commit: impl FnOnce() + Send + 'static) -> crate::Result<()> {
+    let text = serde_json::to_string(&value)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or_default();
+    self
+      .with_conn(move |conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+          "INSERT INTO deno_kv (key, value, updated_at) VALUES (?1, ?2, ?3) ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+          params![key, text, now],
+        )?;
+        tx.commit()?;
+        on_commit();
+        Ok(())
+      })
+      .await
+  }
+
+  /// 返回是否删除了已有的键 键不存在时不调用 on_commit
+  pub async fn delete(&self, key: String, on_commit: impl FnOnce() + Send + 'static) -> crate::Result<bool> {
+    self
+      .with_conn(move |conn| {
+        let tx = conn.transaction()?;
+        let deleted = tx.execute("DELETE FROM deno_kv WHERE key = ?1", params![key])? > 0;
+        tx.commit()?;
+        if deleted {
+          on_commit();
+        }
+        Ok(deleted)
+      })
+      .await
+  }
+
+  /// 按键排序 prefix 为空时返回全部
+  pub async fn list(&self, prefix: Option<String>) -> crate::Result<Vec<KvEntry>> {
+    let prefix = prefix.unwrap_or_default();
+    self
+      .with_conn(move |conn| {
+        let mut statement = conn.prepare("SELECT key, value FROM deno_kv WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key")?;
+        let rows = statement.query_map(params![prefix], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        let mut entries = Vec::new();
+        for row in rows {
+          let (key, value) = row?;
+          entries.push(KvEntry { key, value: serde_json::from_str(&value)? });
+        }
+        Ok(entries)
+      })
+      .await
+  }
+}
@@ -1,25 +1,71 @@
 #[cfg(desktop)]
 mod desktop;
-use desktop::DenoManager;
+use desktop::DenoConfig;
+pub use desktop::DenoManager;
+use queue::IpcQueue;
+use schema::SchemaRegistry;
 use tauri::{
-  plugin::{Builder, TauriPlugin},
-  Manager, Runtime,
+  plugin::{Builder as PluginBuilder, TauriPlugin},
+  Manager, RunEvent, Runtime, WindowEvent,
 };
 
-use deno_lib::deno_ipc::{events_manager::EventsManager, IpcReceiver, IpcSender};
+use deno_lib::deno_ipc::{events_manager::EventsManager, IpcSender};
 
+use host::{HostCommands, HostHandler};
+use serde::{de::DeserializeOwned, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::Mutex;
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub use models::*;
 
+mod binary;
+mod broker;
 mod commands;
+mod envelope;
 mod error;
+mod extension;
+mod fanout;
+mod fetch;
+mod host;
+mod kv;
+mod lifecycle;
 mod models;
+mod queue;
+mod registry;
+mod reliable;
+mod rpc;
+mod schema;
+mod scope;
+mod stream;
+mod subscriptions;
+mod supervisor;
+mod target;
+mod window;
 
+pub use binary::BinaryPayload;
+pub use broker::{topic_matches, PublishMessage};
+pub use deno_lib::deno_runtime::deno_core;
+pub use envelope::MessageSender;
 pub use error::Error;
+use extension::{ExtensionFactory, WorkerExtensions};
+pub use fanout::{DeliveryStats, FanoutConfig, TargetStats};
+use fetch::{FetchConfig, FetchGrant};
+pub use kv::{KvChange, KvEntry};
+use lifecycle::CloseGuard;
+pub use lifecycle::LifecycleEvent;
+pub use queue::{OverflowPolicy, QueueConfig, QueueStats};
+pub use registry::{WorkersRegistry, WorkersSnapshot};
+pub use schema::Direction;
+pub use scope::{check_reserved, glob_match, ScopeEntry};
+pub use stream::StreamMessage;
+pub use subscriptions::HostSubscription;
+pub use supervisor::RouterHealth;
+pub use target::WindowTarget;
+pub use window::WindowPermission;
+use window::{WindowGrant, WindowManager};
 
 pub type WorkersTable = Mutex<HashMap<String, WorkerManager>>;
 
@@ -28,8 +74,8 @@ pub type ManagerMap = Arc<Mutex<HashMap<String, EventsManager>>>;
 pub trait DenoExt<R: Runtime> {
   fn deno(&self) -> &DenoManager<R>;
   fn sender(&self) -> IpcSender;
-  fn receiver(&self) -> IpcReceiver;
-  fn workers_table(&self) -> Arc<RwLock<HashMap<String, WorkerManager>>>;
+  fn queue(&self) -> IpcQueue;
+  fn workers_table(&self) -> WorkersRegistry;
 }
 
 impl<R: Runtime, T: Manager<R>> crate::DenoExt<R> for T {
@@ -39,33 +85,272 @@ impl<R: Runtime, T: Manager<R>> crate::DenoExt<R> for T {
   fn sender(&self) -> IpcSender {
     self.state::<DenoManager<R>>().inner().deno_sender.clone()
   }
-  fn receiver(&self) -> IpcReceiver {
-    self.state::<DenoManager<R>>().inner().deno_receiver.clone()
+  fn queue(&self) -> IpcQueue {
+    self.state::<DenoManager<R>>().inner().queue.clone()
   }
-  fn workers_table(&self) -> Arc<RwLock<HashMap<String, WorkerManager>>> {
+  fn workers_table(&self) -> WorkersRegistry {
     self.state::<DenoManager<R>>().inner().workers_table.clone()
   }
 }
 
+/// 插件构建器
+/// main_module deno 主进程的模块
+/// queue_config deno 到 webview 的消息队列容量及满载策略
+/// fanout_config 路由投递到 worker 的超时 每个 worker 的积压上限及满载策略
+/// schemas 各事件载荷的 json schema
+/// commands worker 可调用的 rust 命令
+/// window_grants worker 的窗口操作授权
+/// close_guard 窗口关闭前询问的 worker
+/// extensions worker 启动时安装的 deno_core 扩展
+/// kv_path 键值存储的 sqlite 文件 默认在应用数据目录下
+/// fetch_config deno:// 协议的 webview 授权及分片超时
+pub struct Builder {
+  main_module: String,
+  queue_config: QueueConfig,
+  fanout_config: FanoutConfig,
+  schemas: Vec<(String, Direction, serde_json::Value)>,
+  commands: HashMap<String, HostHandler>,
+  window_grants: Vec<WindowGrant>,
+  close_guard: Option<CloseGuard>,
+  extensions: Vec<ExtensionFactory>,
+  kv_path: Option<std::path::PathBuf>,
+  fetch_config: FetchConfig,
+}
+
+impl Builder {
+  pub fn new(main_module: impl Into<String>) -> Self {
+    Self {
+      main_module: main_module.into(),
+      queue_config: QueueConfig::default(),
+      fanout_config: FanoutConfig::default(),
+      schemas: Vec::new(),
+      commands: HashMap::new(),
+      window_grants: Vec::new(),
+      close_guard: None,
+      extensions: Vec::new(),
+      kv_path: None,
+      fetch_config: FetchConfig::default(),
+    }
+  }
+
+  /// 设置消息队列容量及满载策略
+  /// Error 策略下 send_to_all_deno 返回 queueFull; worker 发出的消息被拒绝时只计入 deno_queue_stats
+  pub fn ipc_queue(mut self, capacity: usize, policy: OverflowPolicy) -> Self {
+    self.queue_config = QueueConfig { capacity, policy };
+    self
+  }
+
+  /// 设置路由投递到单个 worker 的超时 超时的消息计入 deno_delivery_stats
+  pub fn delivery_timeout(mut self, timeout: std::time::Duration) -> Self {
+    self.fanout_config.timeout = timeout;
+    self
+  }
+
+  /// 设置每个 worker 待投递消息的上限及满载策略 默认 256 条, 丢弃新消息
+  /// 路由不会为单个 worker 阻塞, Block 和 Error 与 DropNewest 相同; worker 等待的应答不受限制
+  pub fn delivery_lane(mut self, capacity: usize, policy: OverflowPolicy) -> Self {
+    self.fanout_config.capacity = capacity;
+    self.fanout_config.policy = policy;
+    self
+  }
+
+  /// 为指定事件和方向注册 json schema, 不符合的载荷在跨越 webview 与 deno 之前被拒绝
+  pub fn schema(mut self, event: impl Into<String>, direction: Direction, schema: serde_json::Value) -> Self {
+    self.schemas.push((event.into(), direction, schema));
+    self
+  }
+
+  /// 注册 worker 可调用的 rust 命令 参数和返回值以 json 传递, Err 在 worker 端以异常抛出
+  /// 例: .command("sum", |nums: Vec<i64>| async move { Ok::<_, String>(nums.iter().sum::<i64>()) })
+  pub fn command<Args, Resp, E, Fut, F>(mut self, name: impl Into<String>, f: F) -> Self
+  where
+    Args: DeserializeOwned + Send + 'static,
+    Resp: Serialize + Send + 'static,
+    E: std::fmt::Display + Send + 'static,
+    Fut: Future<Output = std::result::Result<Resp, E>> + Send + 'static,
+    F: Fn(Args) -> Fut + Send + Sync + 'static,
+  {
+    self.commands.insert(name.into(), HostCommands::handler(f));
+    self
+  }
+
+  /// 授权 worker 操作窗口 worker 和 windows 为 label 通配, 未授权的操作一律拒绝
+  /// 例: .window_permission("sync", "conflict-*", [WindowPermission::Create, WindowPermission::Close])
+  pub fn window_permission(mut self, worker: impl Into<String>, windows: impl Into<String>, permissions: impl IntoIterator<Item = WindowPermission>) -> Self {
+    self.window_grants.push(WindowGrant {
+      worker: worker.into(),
+      windows: windows.into(),
+      permissions: permissions.into_iter().collect(),
+    });
+    self
+  }
+
+  /// 窗口关闭前询问指定 worker, worker 以 onCloseRequested 注册, 返回 false 时阻止关闭
+  /// 在插件的窗口事件中执行 对所有窗口生效, 不依赖页面是否加载 guest-js; 超过 timeout 未回复或出错时照常关闭
+  pub fn close_guard(mut self, worker: impl Into<String>, timeout: std::time::Duration) -> Self {
+    self.close_guard = Some(CloseGuard::new(worker.into(), timeout));
+    self
+  }
+
+  /// 注册 deno_core 扩展 每个 worker 启动时调用 f 创建并安装, 用于以 op 暴露原生功能
+  /// 脚本无法直接访问 Deno.core, 扩展需自带 esm 入口暴露 op; 只需暴露 op 时使用 ops
+  /// 例: .extension(|| image_ext::init_ops_and_esm())
+  pub fn extension(mut self, f: impl Fn() -> deno_core::Extension + Send + Sync + 'static) -> Self {
+    self.extensions.push(Arc::new(f));
+    self
+  }
+
+  /// 注册一组 op 同步和异步 op 均可, 脚本中以 worker-js 的 hostOps(name).<op 名> 调用
+  /// 例: .ops("image", vec![op_decode_image()]) 脚本中 await hostOps("image").op_decode_image(bytes)
+  pub fn ops(mut self, name: &'static str, ops: Vec<deno_core::OpDecl>) -> Self {
+    self.extensions.push(WorkerExtensions::from_ops(name, ops));
+    self
+  }
+
+  /// 设置键值存储的 sqlite 文件 不设置时为应用数据目录下的 deno_kv.sqlite
+  pub fn kv_path(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+    self.kv_path = Some(path.into());
+    self
+  }
+
+  /// 授权 webview 以 deno:// 协议访问 worker 的 serve 处理函数 均为 label 通配, 未授权的请求返回 403
+  /// 响应不带默认的跨域头, 需要时由处理函数自行设置
+  /// 响应体在插件中收齐后一次性返回, 不支持 SSE 等持续输出的响应, 流式数据用 stream_deno
+  /// 例: .fetch_permission("main", "main")
+  pub fn fetch_permission(mut self, webview: impl Into<String>, worker: impl Into<String>) -> Self {
+    self.fetch_config.grants.push(FetchGrant {
+      webview: webview.into(),
+      worker: worker.into(),
+    });
+    self
+  }
+
+  /// 设置 deno:// 响应两个分片之间的超时 默认 30 秒
+  /// 响应在插件中合并后一次性返回, 不适合无结束的响应
+  pub fn fetch_timeout(mut self, timeout: std::time::Duration) -> Self {
+    self.fetch_config.timeout = timeout;
+    self
+  }
+
+  pub fn build<R: Runtime>(self) -> TauriPlugin<R> {
+    let Builder {
+      main_module,
+      queue_config,
+      fanout_config,
+      schemas,
+      commands,
+      window_grants,
+      close_guard,
+      extensions,
+      kv_path,
+      fetch_config,
+    } = self;
+    PluginBuilder::new("deno")
+      .invoke_handler(tauri::generate_handler![
+        commands::check_deno_channel,
+        commands::send_to_all_deno,
+        commands::send_to_deno,
+        commands::send_binary_to_deno,
+        commands::create_deno_channel,
+        commands::unlisten_from,
+        commands::listen_on,
+        commands::close_deno_channel,
+        commands::clean_deno_channel,
+        commands::invoke_deno,
+        commands::stream_deno,
+        commands::cancel_deno_stream,
+        commands::deno_queue_stats,
+        commands::deno_delivery_stats,
+        commands::deno_router_health,
+        commands::publish_topic,
+        commands::subscribe_topic,
+        commands::unsubscribe_topic,
+        commands::ack_deno_channel,
+        commands::confirm_window_close,
+        commands::kv_get,
+        commands::kv_set,
+        commands::kv_delete,
+        commands::kv_list
+      ])
+      .setup(move |app, _api: tauri::plugin::PluginApi<R, ()>| {
+        let app_ref = app.clone();
+        #[cfg(desktop)]
+        let schemas = SchemaRegistry::compile(schemas)?;
+        let kv_path = match kv_path {
+          Some(path) => path,
+          None => app.path().app_data_dir()?.join(kv::KV_FILE),
+        };
+        let config = DenoConfig {
+          main_module,
+          queue: queue_config,
+          fanout: fanout_config,
+          schemas,
+          commands: HostCommands::new(commands),
+          windows: WindowManager::new(window_grants),
+          close_guard,
+          extensions: WorkerExtensions::new(extensions),
+          kv: kv::KvStore::open(&kv_path)?,
+          fetch: fetch_config,
+        };
+        let deno = desktop::init(&app_ref, config)?;
+        app.manage(deno);
+        Ok(())
+      })
+      .register_asynchronous_uri_scheme_protocol(fetch::FETCH_SCHEME, |ctx, request, responder| {
+        //deno://localhost/<worker>/<path> 交给 worker 的 serve 处理函数 不占用本地端口
+        let app = ctx.app_handle().clone();
+        let webview = ctx.webview_label().to_string();
+        tauri::async_runtime::spawn(async move {
+          let Some(deno) = app.try_state::<DenoManager<R>>() else {
+            responder.respond(tauri::http::Response::builder().status(503).body(b"deno plugin not ready".to_vec()).unwrap());
+            return;
+          };
+          let deno = deno.inner().clone();
+          responder.respond(deno.fetch(&webview, request).await);
+        });
+      })
+      .on_window_ready(|window| {
+        if let Some(deno) = window.try_state::<DenoManager<R>>() {
+          deno.notify_lifecycle(LifecycleEvent::WindowCreated { label: window.label().to_string() });
+          deno.refresh_visibility();
+        }
+      })
+      .on_event(|app, event| {
+        //转发窗口及应用生命周期 移动缩放等高频事件只用于重新计算可见状态
+        let Some(deno) = app.try_state::<DenoManager<R>>() else {
+          return;
+        };
+        if let RunEvent::WindowEvent {
+          label,
+          event: WindowEvent::CloseRequested { api, .. },
+          ..
+        } = event
+        {
+          //先阻止关闭 确认后由插件销毁窗口
+          if deno.close_guard.is_some() {
+            api.prevent_close();
+          }
+          deno.guard_close(label);
+        }
+        let (lifecycle, refresh) = LifecycleEvent::from_run_event(event);
+        if let Some(lifecycle) = lifecycle {
+          deno.notify_lifecycle(lifecycle);
+        }
+        if refresh {
+          deno.refresh_visibility();
+        }
+      })
+      .on_drop(|app| {
+        //停止路由任务 不再重启
+        if let Some(deno) = app.try_state::<DenoManager<R>>() {
+          deno.router.shutdown();
+        }
+      })
+      .build()
+  }
+}
+
 /// Initializes the plugin.
 pub fn init<R: Runtime>(main_module: String) -> TauriPlugin<R> {
-  Builder::new("deno")
-    .invoke_handler(tauri::generate_handler![
-      commands::check_deno_channel,
-      commands::send_to_all_deno,
-      commands::send_to_deno,
-      commands::create_deno_channel,
-      commands::unlisten_from,
-      commands::listen_on,
-      commands::close_deno_channel,
-      commands::clean_deno_channel
-    ])
-    .setup(|app, _api: tauri::plugin::PluginApi<R, ()>| {
-      let app_ref = app.clone();
-      #[cfg(desktop)]
-      let deno = desktop::init(&app_ref, main_module)?;
-      app.manage(deno);
-      Ok(())
-    })
-    .build()
+  Builder::new(main_module).build()
 }
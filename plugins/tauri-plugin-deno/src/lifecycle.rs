@@ -0,0 +1,133 @@
+use std::{
+  collections::HashSet,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, RunEvent, Runtime, Theme, WindowEvent};
+
+/// 插件 -> deno 窗口及应用生命周期 {kind, ...}
+pub const LIFECYCLE_EVENT: &str = "deno:lifecycle";
+/// 关闭确认 以 handle 注册在守护 worker 中, 参数 {label} 返回 false 时阻止关闭
+pub const CLOSE_REQUESTED_METHOD: &str = "deno:close-requested";
+/// 插件 -> webview worker 重启后发送 {key}, 该 worker 的旧通道已关闭
+pub const WORKER_RESTARTED_EVENT: &str = "deno:worker-restarted";
+
+/// 转发给 worker 的生命周期事件
+/// 移动和缩放过于频繁不转发, 只用于重新计算 visibility
+/// tauri 没有应用挂起事件, RunEvent::Resumed 在桌面端只在启动时触发一次, 因此不转发应用的挂起和恢复;
+/// 需要感知前后台时使用 Visibility
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum LifecycleEvent {
+  WindowCreated {
+    label: String,
+  },
+  WindowDestroyed {
+    label: String,
+  },
+  WindowFocused {
+    label: String,
+  },
+  WindowBlurred {
+    label: String,
+  },
+  CloseRequested {
+    label: String,
+  },
+  ThemeChanged {
+    label: String,
+    theme: String,
+  },
+  /// 是否有可见且未最小化的窗口 只在变化时发送
+  Visibility {
+    visible: bool,
+  },
+  AppReady,
+  ExitRequested {
+    code: Option<i32>,
+  },
+  Exit,
+}
+
+impl LifecycleEvent {
+  /// 对应的生命周期事件 以及是否需要重新计算 visibility
+  pub fn from_run_event(event: &RunEvent) -> (Option<Self>, bool) {
+    match event {
+      RunEvent::Ready => (Some(LifecycleEvent::AppReady), true),
+      RunEvent::Resumed => (None, true),
+      RunEvent::ExitRequested { code, .. } => (Some(LifecycleEvent::ExitRequested { code: *code }), false),
+      RunEvent::Exit => (Some(LifecycleEvent::Exit), false),
+      RunEvent::WindowEvent { label, event, .. } => {
+        let label = label.clone();
+        match event {
+          WindowEvent::Focused(true) => (Some(LifecycleEvent::WindowFocused { label }), true),
+          WindowEvent::Focused(false) => (Some(LifecycleEvent::WindowBlurred { label }), true),
+          WindowEvent::CloseRequested { .. } => (Some(LifecycleEvent::CloseRequested { label }), false),
+          WindowEvent::Destroyed => (Some(LifecycleEvent::WindowDestroyed { label }), true),
+          WindowEvent::ThemeChanged(theme) => (Some(LifecycleEvent::ThemeChanged { label, theme: theme_name(theme) }), false),
+          WindowEvent::Resized(_) => (None, true),
+          _ => (None, false),
+        }
+      }
+      _ => (None, false),
+    }
+  }
+}
+
+fn theme_name(theme: &Theme) -> String {
+  match theme {
+    Theme::Light => "light".to_string(),
+    Theme::Dark => "dark".to_string(),
+    _ => "unknown".to_string(),
+  }
+}
+
+/// 记录上一次的可见状态 变化时才通知
+#[derive(Clone, Default)]
+pub struct VisibilityTracker {
+  visible: Arc<Mutex<Option<bool>>>,
+}
+
+impl VisibilityTracker {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// 重新计算 有变化时返回 Visibility 事件
+  pub fn refresh<R: Runtime>(&self, app: &AppHandle<R>) -> Option<LifecycleEvent> {
+    let visible = app.webview_windows().values().any(|window| window.is_visible().unwrap_or_default() && !window.is_minimized().unwrap_or_default());
+    let mut last = self.visible.lock().unwrap();
+    if *last == Some(visible) {
+      return None;
+    }
+    *last = Some(visible);
+    Some(LifecycleEvent::Visibility { visible })
+  }
+}
+
+/// 窗口关闭前询问的 worker 及等待时间
+/// worker 不存在, 出错或超时均允许关闭 避免窗口无法关闭
+/// pending 为正在确认的窗口 确认期间重复的关闭请求直接阻止, 不再询问
+#[derive(Debug, Clone)]
+pub struct CloseGuard {
+  pub worker: String,
+  pub timeout: Duration,
+  pending: Arc<Mutex<HashSet<String>>>,
+}
+
+impl CloseGuard {
+  pub fn new(worker: String, timeout: Duration) -> Self {
+    Self { worker, timeout, pending: Default::default() }
+  }
+
+  /// 开始确认 该窗口已在确认中时返回 false
+  pub fn begin(&self, label: &str) -> bool {
+    self.pending.lock().unwrap().insert(label.to_string())
+  }
+
+  pub fn finish(&self, label: &str) {
+    self.pending.lock().unwrap().remove(label);
+  }
+}
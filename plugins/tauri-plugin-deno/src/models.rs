@@ -12,47 +12,58 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::sync_channel;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use tokio::select;
 
+use crate::extension::WorkerExtensions;
+
 macro_rules! svec {
   ($($x:expr),* $(,)?) => (vec![$($x.to_string().into()),*]);
 }
-#[derive(Clone)]
+/// worker 释放时终止 deno 进程, 共享时使用 Arc<WorkerManager> 不要复制
+/// worker_handle 为 run 启动时的句柄, watch_handle 为 run_with_watch 启动时的句柄
 pub struct WorkerManager {
   pub main_nodule: String,
   pub worker_handle: Option<MainWorkerHandle>,
+  pub watch_handle: Option<WatchHandle>,
   pub events_manager: EventsManager,
 }
 
 impl WorkerManager {
-  pub fn new(key: String, main_path: String, deno_sender: IpcSender) -> WorkerManager {
+  pub fn new(key: String, main_path: String, deno_sender: IpcSender, extensions: WorkerExtensions) -> WorkerManager {
     #[cfg(not(debug_assertions))]
     {
-      WorkerManager::run(key,main_path,deno_sender)
+      WorkerManager::run(key, main_path, deno_sender, extensions)
     }
     #[cfg(debug_assertions)]
     {
-      WorkerManager::run_with_watch(key,main_path,deno_sender)
+      WorkerManager::run_with_watch(key, main_path, deno_sender, extensions)
     }
   }
-  pub fn run_with_watch(key: String, main_path: String, deno_sender: IpcSender) -> WorkerManager {
+  pub fn run_with_watch(key: String, main_path: String, deno_sender: IpcSender, extensions: WorkerExtensions) -> WorkerManager {
     let events_manager = EventsManager::new();
     let events_manager_ref = events_manager.clone();
     let main_path_ref = main_path.clone();
+    //文件变化时 watcher 会重新创建 isolate, 句柄始终指向当前的 isolate
+    let (stop_sender, stop_receiver) = async_channel::bounded::<u8>(1);
+    let isolate: Arc<Mutex<Option<v8::IsolateHandle>>> = Default::default();
+    let watch_handle = WatchHandle { sender: stop_sender, isolate: isolate.clone() };
     let build = thread::Builder::new().name(format!("deno-engine-{}", key));
     let _ = build.spawn(move || {
-      let args = svec!["", "run", "--allow-all", main_path.as_str()];
+      //worker 的 key 作为脚本参数传入 deno 端通过 Deno.args[0] 获取
+      let args = svec!["", "run", "--allow-all", main_path.as_str(), key.as_str()];
       // 将args转换为flagset
       let flags = Arc::new(flags_from_vec(args).unwrap());
-      let future = util::file_watcher::watch_recv_ipc(
+      let watcher = util::file_watcher::watch_recv_ipc(
         flags,
         deno_sender.clone(),
         events_manager.clone(),
         util::file_watcher::PrintConfig::new_with_banner("Watcher", "Process", true),
         WatcherRestartMode::Automatic,
         move |flags, deno_sender_ref, events_manager_ref, watcher_communicator, _changed_paths| {
+          let extensions = extensions.clone();
+          let isolate = isolate.clone();
           Ok(async move {
             let factory = CliFactory::from_flags_for_watcher(flags, watcher_communicator.clone());
             let cli_options = factory.cli_options()?;
@@ -62,21 +73,38 @@ impl WorkerManager {
             factory.ipc_state_resolver_new(deno_sender_ref, events_manager_ref).await;
             let _ = watcher_communicator.watch_paths(cli_options.watch_paths());
 
-            let worker = factory.create_cli_main_worker_factory().await?.create_main_worker(WorkerExecutionMode::Run, main_module.clone()).await?;
+            //安装宿主注册的扩展
+            let permissions = factory.root_permissions_container()?.clone();
+            let mut worker = factory
+              .create_cli_main_worker_factory()
+              .await?
+              .create_custom_worker(WorkerExecutionMode::Run, main_module.clone(), permissions, extensions.build(), Default::default())
+              .await?;
+            *isolate.lock().unwrap() = Some(worker.worker.js_runtime.v8_isolate().thread_safe_handle());
             worker.run_for_watcher().await?;
             Ok(())
           })
         },
       );
-      let _ = create_and_run_current_thread(future);
+      //收到终止信号时丢弃 watcher 及正在运行的 worker
+      let future = async move {
+        select! {
+          _ = stop_receiver.recv() => {}
+          res = watcher => {
+            println!("watcher {:?}", res);
+          }
+        }
+      };
+      create_and_run_current_thread(future);
     });
     WorkerManager {
       worker_handle: None,
+      watch_handle: Some(watch_handle),
       main_nodule: main_path_ref,
       events_manager: events_manager_ref,
     }
   }
-  pub fn run(key: String, main_path: String, deno_sender: IpcSender) -> WorkerManager {
+  pub fn run(key: String, main_path: String, deno_sender: IpcSender, extensions: WorkerExtensions) -> WorkerManager {
     let events_manager = EventsManager::new();
     let events_manager_ref = events_manager.clone();
     let main_path_ref = main_path.clone();
@@ -86,7 +114,8 @@ impl WorkerManager {
     let build = thread::Builder::new().name(format!("deno-engine-{}", key));
     // 隐藏的线程任务，用于执行JavaScript引擎的初始化和运行"resource/main.ts".into()
     let _ = build.spawn(move || {
-      let args = svec!["", "run", "--allow-all", main_path.as_str()];
+      //worker 的 key 作为脚本参数传入 deno 端通过 Deno.args[0] 获取
+      let args = svec!["", "run", "--allow-all", main_path.as_str(), key.as_str()];
       // 将args转换为flagset
       let flags = Arc::new(flags_from_vec(args).unwrap());
 
@@ -101,8 +130,9 @@ impl WorkerManager {
         // 创建CLI主工作线程工厂实例
         let worker_factory = factory.create_cli_main_worker_factory().await.unwrap();
 
-        // 创建自定义工作线程实例
-        let mut main_worker: deno_lib::worker::CliMainWorker = worker_factory.create_main_worker(WorkerExecutionMode::Run, main_module.clone()).await.unwrap();
+        // 创建自定义工作线程实例 并安装宿主注册的扩展
+        let permissions = factory.root_permissions_container().unwrap().clone();
+        let mut main_worker: deno_lib::worker::CliMainWorker = worker_factory.create_custom_worker(WorkerExecutionMode::Run, main_module.clone(), permissions, extensions.build(), Default::default()).await.unwrap();
         // 获取工作线程的JavaScript运行时线程安全句柄
         let handle = main_worker.worker.js_runtime.v8_isolate().thread_safe_handle();
         let (sender, receiver) = async_channel::bounded::<u8>(1);
@@ -135,6 +165,7 @@ impl WorkerManager {
     // 创建MainWorkerThread实例
     WorkerManager {
       worker_handle: worker_handle.into(),
+      watch_handle: None,
       main_nodule: main_path_ref,
       events_manager: events_manager_ref,
     }
@@ -145,10 +176,39 @@ impl Drop for WorkerManager {
   fn drop(&mut self) {
     if let Some(worker_handle) = self.worker_handle.clone() {
       worker_handle.clone().terminate();
-      worker_handle.sender.send_blocking(1).expect("error");
+      //worker 可能已经退出 不阻塞也不 panic
+      let _ = worker_handle.sender.try_send(1);
+    }
+    if let Some(watch_handle) = self.watch_handle.take() {
+      watch_handle.terminate();
     }
   }
 }
+
+/// run_with_watch 启动的 worker 句柄
+/// sender 结束 watcher 的事件循环, isolate 为当前运行的 isolate, 用于终止卡住的脚本
+pub struct WatchHandle {
+  sender: async_channel::Sender<u8>,
+  isolate: Arc<Mutex<Option<v8::IsolateHandle>>>,
+}
+
+impl WatchHandle {
+  pub fn terminate(self) {
+    use std::thread::sleep;
+    use std::thread::spawn;
+    use std::time::Duration;
+    //worker 可能已经退出 不阻塞也不 panic
+    let _ = self.sender.try_send(1);
+    let Some(isolate) = self.isolate.lock().unwrap().take() else {
+      return;
+    };
+    //与 MainWorkerHandle 相同 给事件循环 2 秒退出, 仍在执行脚本时强制终止; isolate 已释放时不做任何事
+    spawn(move || {
+      sleep(Duration::from_secs(2));
+      isolate.terminate_execution();
+    });
+  }
+}
 #[derive(Clone)]
 pub struct MainWorkerHandle {
   sender: async_channel::Sender<u8>,
@@ -0,0 +1,247 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::{Arc, Mutex},
+};
+
+use deno_lib::deno_ipc::{messages::IpcMessage, IpcSender};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+/// 队列满时的处理策略
+/// block 阻塞发送方直到有空位, drop_newest 丢弃新消息, drop_oldest 丢弃最旧的消息, error 拒绝新消息并向发送方返回 queueFull
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum OverflowPolicy {
+  #[default]
+  Block,
+  DropNewest,
+  DropOldest,
+  Error,
+}
+
+/// ipc 队列配置
+#[derive(Debug, Clone, Copy)]
+pub struct QueueConfig {
+  pub capacity: usize,
+  pub policy: OverflowPolicy,
+}
+
+impl Default for QueueConfig {
+  fn default() -> Self {
+    Self { capacity: 1024, policy: OverflowPolicy::Block }
+  }
+}
+
+/// 单个消息来源(worker 或 webview)的计数
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceStats {
+  pub received: u64,
+  pub dropped: u64,
+  pub rejected: u64,
+}
+
+/// 队列状态 depth 当前积压 high_water 历史最高积压
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueStats {
+  pub capacity: usize,
+  pub policy: OverflowPolicy,
+  pub depth: usize,
+  pub high_water: usize,
+  pub dropped: u64,
+  pub rejected: u64,
+  pub sources: HashMap<String, SourceStats>,
+}
+
+struct Inner {
+  buffer: VecDeque<(String, IpcMessage)>,
+  stats: QueueStats,
+}
+
+/// 有界 ipc 队列
+/// 每个来源通过 sender 拿到独立的发送端, 由转发任务按策略写入共享队列, 路由通过 recv 读取消息及其来源
+#[derive(Clone)]
+pub struct IpcQueue {
+  config: QueueConfig,
+  inner: Arc<Mutex<Inner>>,
+  readable: Arc<Notify>,
+  writable: Arc<Notify>,
+}
+
+impl IpcQueue {
+  pub fn new(config: QueueConfig) -> Self {
+    let capacity = config.capacity.max(1);
+    let stats = QueueStats {
+      capacity,
+      policy: config.policy,
+      ..Default::default()
+    };
+    Self {
+      config: QueueConfig { capacity, ..config },
+      inner: Arc::new(Mutex::new(Inner {
+        buffer: VecDeque::with_capacity(capacity),
+        stats,
+      })),
+      readable: Arc::new(Notify::new()),
+      writable: Arc::new(Notify::new()),
+    }
+  }
+
+  /// 为指定来源创建发送端
+  /// 发送端容量为 1, block 策略下队列满时转发任务停止读取, 背压传递给发送方
+  /// worker 的发送端拿不到返回值, error 策略下被拒绝的消息只记入 stats
+  pub fn sender(&self, source: impl Into<String>) -> IpcSender {
+    let (sender, receiver) = async_channel::bounded::<IpcMessage>(1);
+    let queue = self.clone();
+    let source = source.into();
+    tokio::task::spawn(async move {
+      while let Ok(msg) = receiver.recv().await {
+        let _ = queue.push(&source, msg).await;
+      }
+    });
+    sender
+  }
+
+  /// 按策略写入队列, error 策略下队列满时返回 QueueFull
+  pub async fn push(&self, source: &str, msg: IpcMessage) -> crate::Result<()> {
+    let mut msg = Some(msg);
+    loop {
+      {
+        let mut inner = self.inner.lock().unwrap();
+        let Inner { buffer, stats } = &mut *inner;
+        let source_stats = stats.sources.entry(source.to_string()).or_default();
+        if buffer.len() < self.config.capacity {
+          source_stats.received += 1;
+          buffer.push_back((source.to_string(), msg.take().unwrap()));
+          stats.depth = buffer.len();
+          stats.high_water = stats.high_water.max(stats.depth);
+          self.readable.notify_one();
+          return Ok(());
+        }
+        match self.config.policy {
+          OverflowPolicy::Block => {}
+          OverflowPolicy::DropNewest => {
+            source_stats.dropped += 1;
+            stats.dropped += 1;
+            return Ok(());
+          }
+          OverflowPolicy::DropOldest => {
+            source_stats.received += 1;
+            source_stats.dropped += 1;
+            stats.dropped += 1;
+            buffer.pop_front();
+            buffer.push_back((source.to_string(), msg.take().unwrap()));
+            self.readable.notify_one();
+            return Ok(());
+          }
+          OverflowPolicy::Error => {
+            source_stats.rejected += 1;
+            stats.rejected += 1;
+            return Err(crate::Error::QueueFull(source.to_string()));
+          }
+        }
+      }
+      self.writable.notified().await;
+    }
+  }
+
+  /// 读取下一条消息及其来源 队列为空时等待
+  pub async fn recv(&self) -> (String, IpcMessage) {
+    loop {
+      {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(msg) = inner.buffer.pop_front() {
+          inner.stats.depth = inner.buffer.len();
+          self.writable.notify_one();
+          return msg;
+        }
+      }
+      self.readable.notified().await;
+    }
+  }
+
+  pub fn stats(&self) -> QueueStats {
+    self.inner.lock().unwrap().stats.clone()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use deno_lib::deno_ipc::messages::SentToDenoMessage;
+  use std::time::Duration;
+
+  fn message(event: &str) -> IpcMessage {
+    IpcMessage::SentToDeno(SentToDenoMessage {
+      id: String::new(),
+      event: event.to_string(),
+      content: serde_json::Value::Null,
+    })
+  }
+
+  fn event_of(msg: IpcMessage) -> String {
+    match msg {
+      IpcMessage::SentToDeno(m) => m.event,
+      _ => unreachable!(),
+    }
+  }
+
+  fn queue(policy: OverflowPolicy) -> IpcQueue {
+    IpcQueue::new(QueueConfig { capacity: 2, policy })
+  }
+
+  #[tokio::test]
+  async fn block_waits_for_space() {
+    let q = queue(OverflowPolicy::Block);
+    q.push("a", message("1")).await.unwrap();
+    q.push("a", message("2")).await.unwrap();
+    let pusher = q.clone();
+    let blocked = tokio::spawn(async move { pusher.push("a", message("3")).await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!blocked.is_finished());
+    assert_eq!(event_of(q.recv().await.1), "1");
+    blocked.await.unwrap().unwrap();
+    assert_eq!(event_of(q.recv().await.1), "2");
+    assert_eq!(event_of(q.recv().await.1), "3");
+    assert_eq!(q.stats().dropped, 0);
+  }
+
+  #[tokio::test]
+  async fn drop_newest_discards_incoming() {
+    let q = queue(OverflowPolicy::DropNewest);
+    for e in ["1", "2", "3"] {
+      q.push("a", message(e)).await.unwrap();
+    }
+    assert_eq!(event_of(q.recv().await.1), "1");
+    assert_eq!(event_of(q.recv().await.1), "2");
+    let stats = q.stats();
+    assert_eq!(stats.dropped, 1);
+    assert_eq!(stats.depth, 0);
+    assert_eq!(stats.sources["a"].received, 2);
+  }
+
+  #[tokio::test]
+  async fn drop_oldest_discards_head() {
+    let q = queue(OverflowPolicy::DropOldest);
+    for e in ["1", "2", "3"] {
+      q.push("a", message(e)).await.unwrap();
+    }
+    assert_eq!(event_of(q.recv().await.1), "2");
+    assert_eq!(event_of(q.recv().await.1), "3");
+    assert_eq!(q.stats().dropped, 1);
+  }
+
+  #[tokio::test]
+  async fn error_rejects_and_returns_queue_full() {
+    let q = queue(OverflowPolicy::Error);
+    q.push("a", message("1")).await.unwrap();
+    q.push("a", message("2")).await.unwrap();
+    let err = q.push("b", message("3")).await.unwrap_err();
+    assert_eq!(err.code(), "queueFull");
+    let stats = q.stats();
+    assert_eq!(stats.rejected, 1);
+    assert_eq!(stats.sources["b"].rejected, 1);
+    assert_eq!(stats.depth, 2);
+  }
+}
@@ -0,0 +1,66 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, PoisonError, RwLock},
+};
+
+use deno_lib::deno_ipc::events_manager::EventsManager;
+
+use crate::WorkerManager;
+
+pub type WorkersSnapshot = Arc<HashMap<String, Arc<WorkerManager>>>;
+
+/// deno worker 注册表
+/// 写时复制: 读取只克隆当前快照的 Arc, 不会等待 worker 启动也不会 panic
+/// 新增/移除时复制一份 map 修改后替换快照, 锁只在替换期间持有
+#[derive(Clone, Default)]
+pub struct WorkersRegistry {
+  workers: Arc<RwLock<WorkersSnapshot>>,
+}
+
+impl WorkersRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// 当前所有 worker 的快照 之后的修改不影响已取得的快照
+  pub fn snapshot(&self) -> WorkersSnapshot {
+    self.workers.read().unwrap_or_else(PoisonError::into_inner).clone()
+  }
+
+  pub fn get(&self, key: &str) -> Option<Arc<WorkerManager>> {
+    self.snapshot().get(key).cloned()
+  }
+
+  pub fn contains(&self, key: &str) -> bool {
+    self.snapshot().contains_key(key)
+  }
+
+  pub fn keys(&self) -> Vec<String> {
+    self.snapshot().keys().cloned().collect()
+  }
+
+  pub fn events_manager(&self, key: &str) -> Option<EventsManager> {
+    self.get(key).map(|worker_manager| worker_manager.events_manager.clone())
+  }
+
+  /// 注册 worker 返回被替换的旧 worker
+  pub fn insert(&self, key: String, worker_manager: WorkerManager) -> Option<Arc<WorkerManager>> {
+    let mut workers = self.workers.write().unwrap_or_else(PoisonError::into_inner);
+    let mut next = HashMap::clone(&workers);
+    let previous = next.insert(key, Arc::new(worker_manager));
+    *workers = Arc::new(next);
+    previous
+  }
+
+  /// 移除 worker 最后一个引用释放时 worker 终止
+  pub fn remove(&self, key: &str) -> Option<Arc<WorkerManager>> {
+    let mut workers = self.workers.write().unwrap_or_else(PoisonError::into_inner);
+    if !workers.contains_key(key) {
+      return None;
+    }
+    let mut next = HashMap::clone(&workers);
+    let removed = next.remove(key);
+    *workers = Arc::new(next);
+    removed
+  }
+}
@@ -0,0 +1,331 @@
+use std::{
+  collections::{BTreeMap, BTreeSet, HashMap},
+  sync::{Arc, Mutex},
+};
+
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tokio::sync::mpsc::Sender;
+
+use crate::{binary::channel_body, commands::ChannelMessage};
+
+/// 未确认消息的上限 超出后丢弃最旧的
+const OUTBOX_LIMIT: usize = 10_000;
+
+/// 已收到序号的记录 low 及之前全部收到, above 为 low 之后零散收到的序号
+/// 并发的命令可能乱序到达, 只记最大值会把较早的序号误判为重复
+#[derive(Default)]
+struct Inbound {
+  low: u64,
+  above: BTreeSet<u64>,
+}
+
+impl Inbound {
+  fn contains(&self, seq: u64) -> bool {
+    seq <= self.low || self.above.contains(&seq)
+  }
+
+  fn insert(&mut self, seq: u64) {
+    if seq <= self.low {
+      return;
+    }
+    self.above.insert(seq);
+    while self.above.remove(&(self.low + 1)) {
+      self.low += 1;
+    }
+    //缺口一直补不上时(webview 放弃重发) 放弃最早的缺口 避免无限增长
+    if self.above.len() > OUTBOX_LIMIT {
+      if let Some(first) = self.above.pop_first() {
+        self.low = first;
+      }
+      while self.above.remove(&(self.low + 1)) {
+        self.low += 1;
+      }
+    }
+  }
+}
+
+struct Inner {
+  channel: Option<Channel>,
+  /// 重新连接后页面还没有注册监听, 此时发出的消息会被页面丢弃 只进入 outbox
+  ready: bool,
+  next_seq: u64,
+  outbox: BTreeMap<u64, ChannelMessage>,
+  inbound: Inbound,
+}
+
+/// 可靠通道状态
+/// 发往 webview 的消息带递增序号, 在收到 ack 之前保存在 outbox, 重新连接后页面注册监听时重发
+/// 来自 webview 的消息按序号去重, 序号在整个会话内递增 重新连接后继续沿用
+pub struct ReliableState {
+  inner: Mutex<Inner>,
+}
+
+impl ReliableState {
+  pub fn new(channel: Channel) -> Self {
+    Self {
+      inner: Mutex::new(Inner {
+        channel: Some(channel),
+        ready: true,
+        next_seq: 1,
+        outbox: BTreeMap::new(),
+        inbound: Inbound::default(),
+      }),
+    }
+  }
+
+  /// 分配序号并发送 发送失败时保留在 outbox 等待重连
+  pub fn deliver(&self, event: String, content: serde_json::Value) {
+    let mut inner = self.inner.lock().unwrap();
+    let seq = inner.next_seq;
+    inner.next_seq += 1;
+    let message = ChannelMessage { event, content, seq: Some(seq) };
+    let failed = inner.ready && inner.channel.as_ref().is_some_and(|channel| channel.send(Self::body(&message)).is_err());
+    if failed {
+      inner.channel = None;
+    }
+    inner.outbox.insert(seq, message);
+    if inner.outbox.len() > OUTBOX_LIMIT {
+      inner.outbox.pop_first();
+      println!("reliable deno channel outbox is full, dropped the oldest message");
+    }
+  }
+
+  /// 确认 seq 及之前的所有消息
+  pub fn ack(&self, seq: u64) {
+    let mut inner = self.inner.lock().unwrap();
+    inner.outbox.retain(|s, _| *s > seq);
+  }
+
+  /// webview 重新连接 替换 channel, 未确认的消息等页面注册监听后由 resume 重发
+  /// 通道创建完成之前页面还拿不到 rid, 也没有监听, 此时重发的消息会丢失
+  pub fn attach(&self, channel: Channel) {
+    let mut inner = self.inner.lock().unwrap();
+    inner.channel = Some(channel);
+    inner.ready = false;
+  }
+
+  /// 页面注册监听后按序号重发所有未确认的消息 之后的消息直接发送
+  /// 已经 ready 时不重复发送
+  pub fn resume(&self) {
+    let mut inner = self.inner.lock().unwrap();
+    if inner.ready {
+      return;
+    }
+    let Some(channel) = inner.channel.clone() else {
+      return;
+    };
+    for message in inner.outbox.values() {
+      if channel.send(Self::body(message)).is_err() {
+        inner.channel = None;
+        return;
+      }
+    }
+    inner.ready = true;
+  }
+
+  /// webview 断开 之后的消息只进入 outbox
+  pub fn detach(&self) {
+    self.inner.lock().unwrap().channel = None;
+  }
+
+  /// 来自 webview 的序号是否已经投递过
+  pub fn is_received(&self, seq: u64) -> bool {
+    self.inner.lock().unwrap().inbound.contains(seq)
+  }
+
+  /// 消息已交给 deno 后记录序号 发送失败时不记录, webview 重发时仍会投递
+  pub fn received(&self, seq: u64) {
+    self.inner.lock().unwrap().inbound.insert(seq);
+  }
+
+  /// 二进制内容与非可靠通道一样以原始帧发送, 帧中带上序号
+  fn body(message: &ChannelMessage) -> InvokeResponseBody {
+    channel_body(&message.event, &message.content, message.seq)
+  }
+}
+
+/// 可靠会话 页面刷新后以相同 session 重新创建通道时继续使用
+/// listeners 为该会话的事件监听, 在会话关闭前一直有效
+#[derive(Clone)]
+pub struct ReliableSession {
+  pub state: Arc<ReliableState>,
+  pub listeners: Arc<tokio::sync::Mutex<HashMap<String, Sender<bool>>>>,
+}
+
+/// 以 worker key + session 保存可靠会话
+#[derive(Clone, Default)]
+pub struct ReliableSessions {
+  sessions: Arc<Mutex<HashMap<(String, String), ReliableSession>>>,
+}
+
+impl ReliableSessions {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// 获取已有会话并重新连接, 不存在时新建
+  pub fn open(&self, key: &str, session: &str, channel: Channel) -> ReliableSession {
+    let mut sessions = self.sessions.lock().unwrap();
+    match sessions.get(&(key.to_string(), session.to_string())) {
+      Some(existing) => {
+        existing.state.attach(channel);
+        existing.clone()
+      }
+      None => {
+        let created = ReliableSession {
+          state: Arc::new(ReliableState::new(channel)),
+          listeners: Default::default(),
+        };
+        sessions.insert((key.to_string(), session.to_string()), created.clone());
+        created
+      }
+    }
+  }
+
+  pub fn close(&self, key: &str, session: &str) {
+    self.sessions.lock().unwrap().remove(&(key.to_string(), session.to_string()));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn recording_channel() -> (Channel, Arc<Mutex<Vec<InvokeResponseBody>>>) {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let sent_ref = sent.clone();
+    let channel = Channel::new(move |body| {
+      sent_ref.lock().unwrap().push(body);
+      Ok(())
+    });
+    (channel, sent)
+  }
+
+  fn failing_channel() -> Channel {
+    Channel::new(|_| Err(tauri::Error::Io(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "closed"))))
+  }
+
+  fn seq_of(body: &InvokeResponseBody) -> Option<u64> {
+    match body {
+      InvokeResponseBody::Json(json) => serde_json::from_str::<ChannelMessage>(json).unwrap().seq,
+      InvokeResponseBody::Raw(frame) => Some(u64::from_be_bytes(frame[4..12].try_into().unwrap())),
+    }
+  }
+
+  #[test]
+  fn inbound_accepts_out_of_order_seqs() {
+    let (channel, _) = recording_channel();
+    let state = ReliableState::new(channel);
+    state.received(2);
+    assert!(!state.is_received(1));
+    assert!(state.is_received(2));
+    state.received(1);
+    state.received(3);
+    assert!((1..=3).all(|seq| state.is_received(seq)));
+    assert!(!state.is_received(4));
+    let inner = state.inner.lock().unwrap();
+    assert_eq!(inner.inbound.low, 3);
+    assert!(inner.inbound.above.is_empty());
+  }
+
+  #[test]
+  fn unrecorded_seq_is_delivered_on_retry() {
+    let (channel, _) = recording_channel();
+    let state = ReliableState::new(channel);
+    //第一次发送失败没有调用 received, 重发时不能视为重复
+    assert!(!state.is_received(1));
+    assert!(!state.is_received(1));
+    state.received(1);
+    assert!(state.is_received(1));
+  }
+
+  #[test]
+  fn inbound_survives_reconnect() {
+    let (channel, _) = recording_channel();
+    let state = ReliableState::new(channel);
+    state.received(1);
+    state.received(2);
+    state.detach();
+    let (channel, _) = recording_channel();
+    state.attach(channel);
+    assert!(state.is_received(1));
+    assert!(state.is_received(2));
+    assert!(!state.is_received(3));
+  }
+
+  #[test]
+  fn inbound_gap_is_bounded() {
+    let (channel, _) = recording_channel();
+    let state = ReliableState::new(channel);
+    for seq in 2..=(OUTBOX_LIMIT as u64 + 2) {
+      state.received(seq);
+    }
+    let inner = state.inner.lock().unwrap();
+    assert!(inner.inbound.above.len() <= OUTBOX_LIMIT);
+    assert_eq!(inner.inbound.low, OUTBOX_LIMIT as u64 + 2);
+  }
+
+  #[test]
+  fn unacked_messages_are_resent_on_attach() {
+    let (channel, sent) = recording_channel();
+    let state = ReliableState::new(channel);
+    state.deliver("a".to_string(), serde_json::json!(1));
+    state.deliver("b".to_string(), serde_json::json!(2));
+    state.deliver("c".to_string(), serde_json::json!(3));
+    assert_eq!(sent.lock().unwrap().iter().map(seq_of).collect::<Vec<_>>(), vec![Some(1), Some(2), Some(3)]);
+    state.ack(2);
+    let (channel, resent) = recording_channel();
+    state.attach(channel);
+    state.resume();
+    assert_eq!(resent.lock().unwrap().iter().map(seq_of).collect::<Vec<_>>(), vec![Some(3)]);
+  }
+
+  #[test]
+  fn reconnect_resends_only_after_resume() {
+    let sessions = ReliableSessions::new();
+    let (channel, _) = recording_channel();
+    let session = sessions.open("main", "s1", channel);
+    session.state.deliver("a".to_string(), serde_json::json!(1));
+    session.state.deliver("b".to_string(), serde_json::json!(2));
+    session.state.detach();
+    //页面刷新后以相同 session 重新创建通道 此时还没有 rid 和监听
+    let (channel, resent) = recording_channel();
+    let reopened = sessions.open("main", "s1", channel);
+    assert!(resent.lock().unwrap().is_empty());
+    reopened.state.deliver("c".to_string(), serde_json::json!(3));
+    assert!(resent.lock().unwrap().is_empty());
+    //注册监听后按顺序重发 之后的消息直接发送
+    reopened.state.resume();
+    assert_eq!(resent.lock().unwrap().iter().map(seq_of).collect::<Vec<_>>(), vec![Some(1), Some(2), Some(3)]);
+    reopened.state.resume();
+    reopened.state.deliver("d".to_string(), serde_json::json!(4));
+    assert_eq!(resent.lock().unwrap().iter().map(seq_of).collect::<Vec<_>>(), vec![Some(1), Some(2), Some(3), Some(4)]);
+  }
+
+  #[test]
+  fn failed_send_keeps_message_for_reconnect() {
+    let state = ReliableState::new(failing_channel());
+    state.deliver("a".to_string(), serde_json::json!(1));
+    //通道失效后的消息只进入 outbox
+    state.deliver("b".to_string(), serde_json::json!(2));
+    let (channel, resent) = recording_channel();
+    state.attach(channel);
+    state.resume();
+    assert_eq!(resent.lock().unwrap().iter().map(seq_of).collect::<Vec<_>>(), vec![Some(1), Some(2)]);
+  }
+
+  #[test]
+  fn binary_messages_are_sent_as_frames_with_seq() {
+    let (channel, sent) = recording_channel();
+    let state = ReliableState::new(channel);
+    state.deliver("json".to_string(), serde_json::json!({ "a": 1 }));
+    state.deliver("bin".to_string(), crate::binary::BinaryPayload::new(vec![1, 2, 3]).into_value());
+    let sent = sent.lock().unwrap();
+    assert!(matches!(sent[0], InvokeResponseBody::Json(_)));
+    let InvokeResponseBody::Raw(frame) = &sent[1] else {
+      panic!("binary message should be a raw frame");
+    };
+    assert_eq!(frame.as_slice(), crate::binary::encode_frame("bin", &[1, 2, 3], Some(2)).as_slice());
+    assert_eq!(seq_of(&sent[1]), Some(2));
+  }
+}
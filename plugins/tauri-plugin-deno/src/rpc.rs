@@ -0,0 +1,98 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use deno_lib::deno_ipc::events_manager::EventsManager;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// webview -> deno 的调用事件, worker 通过 `new Deno.IpcBroadcastChannel("deno:invoke")` 接收
+pub const INVOKE_EVENT: &str = "deno:invoke";
+/// deno -> 插件 的应答事件, 由路由拦截, 不会转发到窗口
+pub const REPLY_EVENT: &str = "deno:reply";
+/// 默认超时时间(毫秒)
+pub const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+
+/// 发送给 worker 的调用请求
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InvokeRequest {
+  pub id: String,
+  pub method: String,
+  pub args: serde_json::Value,
+}
+
+/// worker 返回的应答 error 不为空表示处理函数抛出异常
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InvokeReply {
+  pub id: String,
+  #[serde(default)]
+  pub result: serde_json::Value,
+  #[serde(default)]
+  pub error: Option<String>,
+}
+
+/// 请求/应答管理器
+/// pending 以关联 id 保存等待中的调用及目标 worker, 应答由路由通过 resolve 回填
+/// worker 终止或被替换时 fail_worker 丢弃其等待中的调用, 调用方立即得到 WorkerNotFound
+#[derive(Clone, Default)]
+pub struct RpcManager {
+  pending: Arc<Mutex<HashMap<String, (String, oneshot::Sender<InvokeReply>)>>>,
+}
+
+impl RpcManager {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// 向 worker 发起调用并等待应答
+  pub async fn invoke(&self, events_manager: &EventsManager, key: String, method: String, args: serde_json::Value, timeout: Duration) -> crate::Result<serde_json::Value> {
+    let id = Uuid::new_v4().to_string();
+    let (sender, receiver) = oneshot::channel();
+    self.pending.lock().unwrap().insert(id.clone(), (key.clone(), sender));
+
+    let request = InvokeRequest { id: id.clone(), method, args };
+    let content = serde_json::to_value(request).unwrap_or_default();
+    if events_manager.send(INVOKE_EVENT.to_string(), content).await.is_err() {
+      self.pending.lock().unwrap().remove(&id);
+      return Err(crate::Error::WorkerNotFound(key));
+    }
+
+    match tokio::time::timeout(timeout, receiver).await {
+      Ok(Ok(reply)) => match reply.error {
+        Some(error) => Err(crate::Error::Handler(error)),
+        None => Ok(reply.result),
+      },
+      //发送端被 fail_worker 丢弃 说明 worker 已终止或被替换
+      Ok(Err(_)) => Err(crate::Error::WorkerNotFound(key)),
+      Err(_) => {
+        self.pending.lock().unwrap().remove(&id);
+        Err(crate::Error::Timeout {
+          worker: key,
+          millis: timeout.as_millis() as u64,
+        })
+      }
+    }
+  }
+
+  /// 路由收到 deno 的应答后回填
+  pub fn resolve(&self, content: serde_json::Value) {
+    let reply: InvokeReply = match serde_json::from_value(content) {
+      Ok(reply) => reply,
+      Err(e) => {
+        println!("invalid deno reply:{:?}", e);
+        return;
+      }
+    };
+    if let Some((_, pending)) = self.pending.lock().unwrap().remove(&reply.id) {
+      let _ = pending.send(reply);
+    }
+  }
+
+  /// 丢弃发往指定 worker 的等待中调用
+  pub fn fail_worker(&self, key: &str) {
+    self.pending.lock().unwrap().retain(|_, (worker, _)| worker != key);
+  }
+}
@@ -0,0 +1,43 @@
+use std::{collections::HashMap, sync::Arc};
+
+use jsonschema::JSONSchema;
+use serde::{Deserialize, Serialize};
+
+/// 消息方向 to_deno webview 发往 deno, to_window deno 发往 webview
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+  ToDeno,
+  ToWindow,
+}
+
+/// 事件载荷的 json schema 注册表 以 事件名 + 方向 为键
+/// 未注册 schema 的事件不做校验
+#[derive(Clone, Default)]
+pub struct SchemaRegistry {
+  schemas: Arc<HashMap<(String, Direction), Arc<JSONSchema>>>,
+}
+
+impl SchemaRegistry {
+  /// 编译 builder 中注册的 schema
+  pub fn compile(definitions: Vec<(String, Direction, serde_json::Value)>) -> crate::Result<Self> {
+    let mut schemas = HashMap::new();
+    for (event, direction, schema) in definitions {
+      let compiled = JSONSchema::compile(&schema).map_err(|e| crate::Error::InvalidSchema { event: event.clone(), message: e.to_string() })?;
+      schemas.insert((event, direction), Arc::new(compiled));
+    }
+    Ok(Self { schemas: Arc::new(schemas) })
+  }
+
+  /// 校验载荷 失败时返回所有不符合的位置及原因
+  pub fn validate(&self, event: &str, direction: Direction, payload: &serde_json::Value) -> crate::Result<()> {
+    let Some(schema) = self.schemas.get(&(event.to_string(), direction)) else {
+      return Ok(());
+    };
+    if let Err(errors) = schema.validate(payload) {
+      let message = errors.map(|e| format!("{}: {}", e.instance_path, e)).collect::<Vec<_>>().join("; ");
+      return Err(crate::Error::InvalidPayload { event: event.to_string(), message });
+    }
+    Ok(())
+  }
+}
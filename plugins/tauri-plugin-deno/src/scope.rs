@@ -0,0 +1,164 @@
+use serde::Deserialize;
+use tauri::ipc::{CommandScope, GlobalScope};
+
+/// 插件内部协议事件的前缀 (deno:invoke, deno:fetch, deno:kv-reply 等), webview 不能直接监听或发送
+pub const RESERVED_PREFIX: &str = "deno:";
+
+/// 事件名属于插件保留前缀时返回 PermissionDenied
+pub fn check_reserved(event: &str) -> crate::Result<()> {
+  if event.starts_with(RESERVED_PREFIX) {
+    return Err(crate::Error::PermissionDenied(format!("event `{}` is reserved", event)));
+  }
+  Ok(())
+}
+
+/// 简单通配 `*` 匹配任意字符序列, `?` 匹配单个字符
+/// 事件名来自 webview, 用迭代的双指针匹配 不递归也不会指数回溯
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+  let pattern: Vec<char> = pattern.chars().collect();
+  let text: Vec<char> = text.chars().collect();
+  let (mut p, mut t) = (0, 0);
+  //最近一个 `*` 的位置 以及它当前匹配到的 text 位置
+  let mut star: Option<(usize, usize)> = None;
+  while t < text.len() {
+    match pattern.get(p) {
+      Some('*') => {
+        star = Some((p, t));
+        p += 1;
+      }
+      Some(&c) if c == '?' || c == text[t] => {
+        p += 1;
+        t += 1;
+      }
+      _ => match star {
+        //回到上一个 `*` 让它多匹配一个字符
+        Some((star_p, star_t)) => {
+          p = star_p + 1;
+          t = star_t + 1;
+          star = Some((star_p, star_t + 1));
+        }
+        None => return false,
+      },
+    }
+  }
+  pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// 权限范围 限制 capability 能访问的 worker 和事件名(invoke_deno/stream_deno 为方法名) 未填写的字段表示不限制
+/// topic 只用于 publish_topic/subscribe_topic, 订阅时以订阅的模式本身匹配; 不带 topic 的条目不影响主题命令
+/// 例: { "identifier": "deno:allow-send-to-deno", "allow": [{ "worker": "main", "event": "sync.*" }] }
+/// 例: { "identifier": "deno:allow-publish-topic", "allow": [{ "topic": "sensors.*" }] }
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScopeEntry {
+  pub worker: Option<String>,
+  pub event: Option<String>,
+  pub topic: Option<String>,
+}
+
+impl ScopeEntry {
+  /// event 为 None 时只校验 worker (如创建通道), 此时限定了事件的 deny 条目不生效
+  fn matches(&self, worker: &str, event: Option<&str>, deny: bool) -> bool {
+    let worker_matches = self.worker.as_deref().map_or(true, |pattern| glob_match(pattern, worker));
+    let event_matches = match (self.event.as_deref(), event) {
+      (Some(pattern), Some(event)) => glob_match(pattern, event),
+      (Some(_), None) => !deny,
+      (None, _) => true,
+    };
+    worker_matches && event_matches
+  }
+}
+
+/// 命令范围和全局范围合并后的校验
+/// 命中 deny 拒绝; 配置了 allow 时必须命中其中一条; 都未配置时放行, 兼容不带范围的权限
+pub struct DenoScope {
+  command: CommandScope<ScopeEntry>,
+  global: GlobalScope<ScopeEntry>,
+}
+
+impl DenoScope {
+  pub fn new(command: CommandScope<ScopeEntry>, global: GlobalScope<ScopeEntry>) -> Self {
+    Self { command, global }
+  }
+
+  pub fn is_allowed(&self, worker: &str, event: Option<&str>) -> bool {
+    let mut denies = self.command.denies().iter().chain(self.global.denies().iter());
+    if denies.any(|entry| entry.matches(worker, event, true)) {
+      return false;
+    }
+    let mut allows = self.command.allows().iter().chain(self.global.allows().iter()).peekable();
+    allows.peek().is_none() || allows.any(|entry| entry.matches(worker, event, false))
+  }
+
+  /// 主题的校验 只看带 topic 的条目, 规则与 is_allowed 相同
+  pub fn is_topic_allowed(&self, topic: &str) -> bool {
+    let topic_pattern = |entry: &ScopeEntry| entry.topic.clone();
+    let mut denies = self.command.denies().iter().chain(self.global.denies().iter()).filter_map(topic_pattern);
+    if denies.any(|pattern| glob_match(&pattern, topic)) {
+      return false;
+    }
+    let mut allows = self.command.allows().iter().chain(self.global.allows().iter()).filter_map(topic_pattern).peekable();
+    allows.peek().is_none() || allows.any(|pattern| glob_match(&pattern, topic))
+  }
+
+  /// 主题不允许时返回 PermissionDenied
+  pub fn check_topic(&self, topic: &str) -> crate::Result<()> {
+    if self.is_topic_allowed(topic) {
+      return Ok(());
+    }
+    Err(crate::Error::PermissionDenied(format!("topic `{}`", topic)))
+  }
+
+  /// 不允许时返回 PermissionDenied
+  pub fn check(&self, worker: &str, event: Option<&str>) -> crate::Result<()> {
+    if self.is_allowed(worker, event) {
+      return Ok(());
+    }
+    Err(crate::Error::PermissionDenied(match event {
+      Some(event) => format!("worker `{}` event `{}`", worker, event),
+      None => format!("worker `{}`", worker),
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::glob_match;
+
+  #[test]
+  fn empty_pattern_and_text() {
+    assert!(glob_match("", ""));
+    assert!(!glob_match("", "a"));
+    assert!(!glob_match("a", ""));
+    assert!(glob_match("*", ""));
+    assert!(glob_match("**", ""));
+    assert!(!glob_match("?", ""));
+  }
+
+  #[test]
+  fn single_star_matches_any_sequence() {
+    assert!(glob_match("*", "anything.at.all"));
+    assert!(glob_match("sync.*", "sync."));
+    assert!(glob_match("sync.*", "sync.files.changed"));
+    assert!(!glob_match("sync.*", "async.files"));
+    assert!(glob_match("conflict-?", "conflict-1"));
+    assert!(!glob_match("conflict-?", "conflict-12"));
+  }
+
+  #[test]
+  fn multiple_stars_backtrack() {
+    assert!(glob_match("*a*b*c", "xxaxxbxxc"));
+    assert!(glob_match("a*b*a", "abba"));
+    assert!(!glob_match("*a*b*c", "xxaxxbxx"));
+    assert!(glob_match("*.*.end", "a.b.c.end"));
+    assert!(!glob_match("a*a*a*a*b", &"a".repeat(64)));
+  }
+
+  #[test]
+  fn very_long_input_does_not_overflow() {
+    let text = "a".repeat(1_000_000);
+    assert!(glob_match("*", &text));
+    assert!(glob_match("a*a", &text));
+    assert!(!glob_match("*b", &text));
+    assert!(!glob_match("*a*a*a*a*a*b", &text[..10_000]));
+  }
+}
@@ -0,0 +1,133 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
+
+use deno_lib::deno_ipc::events_manager::EventsManager;
+use serde::{Deserialize, Serialize};
+use tauri::ipc::{Channel, InvokeResponseBody};
+use uuid::Uuid;
+
+use crate::{
+  binary::{encode_frame, BinaryPayload},
+  fanout::Fanout,
+  registry::WorkersRegistry,
+};
+
+/// webview -> deno 的流式请求事件
+pub const STREAM_EVENT: &str = "deno:stream";
+/// webview 取消流式请求
+pub const STREAM_CANCEL_EVENT: &str = "deno:stream-cancel";
+/// deno -> 插件 的分片事件, 由路由拦截后写入对应的 channel
+pub const STREAM_CHUNK_EVENT: &str = "deno:stream-chunk";
+
+/// 推送给 webview 的分片 end/error 之后不会再有消息
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum StreamMessage {
+  Chunk {
+    #[serde(default)]
+    data: serde_json::Value,
+  },
+  End,
+  Error {
+    message: String,
+  },
+}
+
+impl StreamMessage {
+  /// 二进制分片与通道消息一样以原始帧发送 帧的事件名为 chunk, 其余为 json
+  fn body(&self) -> InvokeResponseBody {
+    match self {
+      StreamMessage::Chunk { data } => match BinaryPayload::from_value(data) {
+        Some(payload) => InvokeResponseBody::Raw(encode_frame("chunk", &payload.data, None)),
+        None => InvokeResponseBody::Json(serde_json::to_string(self).unwrap_or_default()),
+      },
+      _ => InvokeResponseBody::Json(serde_json::to_string(self).unwrap_or_default()),
+    }
+  }
+}
+
+/// worker 发来的分片 {id, kind, data | message}
+#[derive(Deserialize, Debug)]
+struct StreamFrame {
+  id: String,
+  #[serde(flatten)]
+  message: StreamMessage,
+}
+
+struct ActiveStream {
+  key: String,
+  channel: Channel,
+}
+
+/// 流式请求管理器
+/// streams 以请求 id 保存接收分片的 channel, 路由按到达顺序写入
+/// 取消通知经 fanout 发往 worker, 路由不等待 worker 接收
+#[derive(Clone)]
+pub struct StreamManager {
+  streams: Arc<Mutex<HashMap<String, ActiveStream>>>,
+  fanout: Fanout,
+  workers: WorkersRegistry,
+}
+
+impl StreamManager {
+  pub fn new(fanout: Fanout, workers: WorkersRegistry) -> Self {
+    Self { streams: Default::default(), fanout, workers }
+  }
+
+  /// 向 worker 发起流式请求 返回请求 id
+  pub async fn start(&self, events_manager: EventsManager, key: String, method: String, args: serde_json::Value, channel: Channel) -> crate::Result<String> {
+    let id = Uuid::new_v4().to_string();
+    let content = serde_json::json!({ "id": id, "method": method, "args": args });
+    self.streams.lock().unwrap().insert(id.clone(), ActiveStream { key: key.clone(), channel });
+    if events_manager.send(STREAM_EVENT.to_string(), content).await.is_err() {
+      self.streams.lock().unwrap().remove(&id);
+      return Err(crate::Error::WorkerNotFound(key));
+    }
+    Ok(id)
+  }
+
+  /// 路由收到分片后写入 channel, 结束或 channel 失效时移除
+  pub fn dispatch(&self, content: serde_json::Value) {
+    let frame: StreamFrame = match serde_json::from_value(content) {
+      Ok(frame) => frame,
+      Err(e) => {
+        println!("invalid deno stream frame:{:?}", e);
+        return;
+      }
+    };
+    let finished = !matches!(frame.message, StreamMessage::Chunk { .. });
+    let removed = {
+      let mut streams = self.streams.lock().unwrap();
+      let Some(stream) = streams.get(&frame.id) else {
+        return;
+      };
+      let broken = stream.channel.send(frame.message.body()).is_err();
+      if finished || broken {
+        streams.remove(&frame.id).map(|stream| stream.key)
+      } else {
+        None
+      }
+    };
+    //webview 已经不在了 通知 worker 停止生产
+    if let (false, Some(key)) = (finished, removed) {
+      self.notify_cancel(&key, &frame.id);
+    }
+  }
+
+  /// 取消流式请求
+  pub fn cancel(&self, id: String) -> bool {
+    let stream = self.streams.lock().unwrap().remove(&id);
+    let Some(stream) = stream else {
+      return false;
+    };
+    let _ = stream.channel.send(StreamMessage::End.body());
+    self.notify_cancel(&stream.key, &id);
+    true
+  }
+
+  fn notify_cancel(&self, key: &str, id: &str) {
+    self.fanout.send(&self.workers.snapshot(), key, STREAM_CANCEL_EVENT.to_string(), serde_json::json!({ "id": id }));
+  }
+}
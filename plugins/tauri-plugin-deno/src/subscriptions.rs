@@ -0,0 +1,165 @@
+use std::{
+  collections::{HashMap, HashSet},
+  pin::Pin,
+  sync::{Arc, Mutex},
+  task::{Context, Poll},
+};
+
+use futures::Stream;
+use tauri::{ipc::Channel, ResourceId};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use uuid::Uuid;
+
+use crate::{binary::channel_body, reliable::ReliableState};
+
+/// 通道的投递端 可靠模式下经 reliable 带序号投递
+#[derive(Clone)]
+pub struct ChannelSink {
+  channel: Channel,
+  reliable: Option<Arc<ReliableState>>,
+}
+
+impl ChannelSink {
+  pub fn new(channel: Channel, reliable: Option<Arc<ReliableState>>) -> Self {
+    Self { channel, reliable }
+  }
+
+  /// 发送失败返回 false 可靠模式下消息保留在 outbox 等待重连, 始终返回 true
+  pub fn send(&self, event: String, content: serde_json::Value) -> bool {
+    if let Some(reliable) = &self.reliable {
+      reliable.deliver(event, content);
+      return true;
+    }
+    self.channel.send(channel_body(&event, &content, None)).is_ok()
+  }
+}
+
+struct Subscription {
+  label: String,
+  rid: ResourceId,
+  sink: ChannelSink,
+}
+
+/// 宿主订阅的缓冲上限 超出后丢弃新消息
+const HOST_CAPACITY: usize = 256;
+
+type Listeners<T> = Arc<Mutex<HashMap<(String, String), HashMap<Uuid, T>>>>;
+
+/// 通道订阅表 以 worker key + 事件名 保存 listen_on 的通道
+/// deno 发往窗口的消息按此表投递到订阅的通道, 与通道收到的其他消息走同一个投递端
+/// hosts 为 rust 代码的订阅, 不区分目标窗口 收到该 worker 发出的所有同名消息
+#[derive(Clone, Default)]
+pub struct ChannelSubscriptions {
+  subscriptions: Listeners<Subscription>,
+  hosts: Listeners<mpsc::Sender<serde_json::Value>>,
+}
+
+impl ChannelSubscriptions {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn add(&self, worker: &str, event: &str, id: Uuid, label: String, rid: ResourceId, sink: ChannelSink) {
+    let mut subscriptions = self.subscriptions.lock().unwrap();
+    subscriptions.entry((worker.to_string(), event.to_string())).or_default().insert(id, Subscription { label, rid, sink });
+  }
+
+  pub fn remove(&self, worker: &str, event: &str, id: &Uuid) {
+    let mut subscriptions = self.subscriptions.lock().unwrap();
+    let key = (worker.to_string(), event.to_string());
+    if let Some(listeners) = subscriptions.get_mut(&key) {
+      listeners.remove(id);
+      if listeners.is_empty() {
+        subscriptions.remove(&key);
+      }
+    }
+  }
+
+  /// rust 代码订阅 worker 发出的事件 返回的 stream 释放时取消订阅
+  pub fn subscribe_host(&self, worker: &str, event: &str) -> HostSubscription {
+    let (sender, receiver) = mpsc::channel(HOST_CAPACITY);
+    let id = Uuid::new_v4();
+    let key = (worker.to_string(), event.to_string());
+    self.hosts.lock().unwrap().entry(key.clone()).or_default().insert(id, sender);
+    HostSubscription { receiver, hosts: self.hosts.clone(), key, id }
+  }
+
+  /// 投递给窗口 label 和通道 rid 满足 filter 的订阅 返回成功投递的窗口 label
+  /// 发送失败的订阅直接移除, 对应的监听任务在下一次发送失败时退出
+  pub fn deliver(&self, worker: &str, event: &str, content: &serde_json::Value, filter: impl Fn(&str, ResourceId) -> bool) -> HashSet<String> {
+    let targets: Vec<(Uuid, String, ChannelSink)> = {
+      let subscriptions = self.subscriptions.lock().unwrap();
+      match subscriptions.get(&(worker.to_string(), event.to_string())) {
+        Some(listeners) => listeners
+          .iter()
+          .filter(|(_, subscription)| filter(&subscription.label, subscription.rid))
+          .map(|(id, subscription)| (*id, subscription.label.clone(), subscription.sink.clone()))
+          .collect(),
+        None => Vec::new(),
+      }
+    };
+    let mut delivered = HashSet::new();
+    for (id, label, sink) in targets {
+      if sink.send(event.to_string(), content.clone()) {
+        delivered.insert(label);
+      } else {
+        self.remove(worker, event, &id);
+      }
+    }
+    delivered
+  }
+
+  /// 投递给宿主订阅 与目标窗口无关, 在解析目标之前调用 目标不存在时宿主也能收到
+  pub fn deliver_hosts(&self, worker: &str, event: &str, content: &serde_json::Value) {
+    let key = (worker.to_string(), event.to_string());
+    let mut hosts = self.hosts.lock().unwrap();
+    let Some(listeners) = hosts.get_mut(&key) else {
+      return;
+    };
+    listeners.retain(|_, sender| match sender.try_send(content.clone()) {
+      Ok(()) => true,
+      Err(TrySendError::Full(_)) => {
+        println!("host subscription of {} {} is full, dropped a message", worker, event);
+        true
+      }
+      Err(TrySendError::Closed(_)) => false,
+    });
+    if listeners.is_empty() {
+      hosts.remove(&key);
+    }
+  }
+}
+
+/// rust 代码对 worker 事件的订阅 实现 Stream, 释放时取消订阅
+pub struct HostSubscription {
+  receiver: mpsc::Receiver<serde_json::Value>,
+  hosts: Listeners<mpsc::Sender<serde_json::Value>>,
+  key: (String, String),
+  id: Uuid,
+}
+
+impl HostSubscription {
+  pub async fn recv(&mut self) -> Option<serde_json::Value> {
+    self.receiver.recv().await
+  }
+}
+
+impl Stream for HostSubscription {
+  type Item = serde_json::Value;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    self.receiver.poll_recv(cx)
+  }
+}
+
+impl Drop for HostSubscription {
+  fn drop(&mut self) {
+    let mut hosts = self.hosts.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+    if let Some(listeners) = hosts.get_mut(&self.key) {
+      listeners.remove(&self.id);
+      if listeners.is_empty() {
+        hosts.remove(&self.key);
+      }
+    }
+  }
+}
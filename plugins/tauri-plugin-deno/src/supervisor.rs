@@ -0,0 +1,128 @@
+use std::{
+  future::Future,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use tokio::sync::watch;
+
+/// 连续重启的最大等待时间
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+/// 运行超过该时间后再失败 不算作连续失败
+const STABLE_AFTER: Duration = Duration::from_secs(30);
+
+/// 路由任务状态 started_at/last_failure_at 为毫秒时间戳
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RouterHealth {
+  pub running: bool,
+  pub restarts: u64,
+  pub started_at: Option<u64>,
+  pub last_failure: Option<String>,
+  pub last_failure_at: Option<u64>,
+}
+
+/// 路由任务守护
+/// 任务 panic 或意外退出时记录原因并重新启动, shutdown 后停止任务不再重启
+#[derive(Clone)]
+pub struct RouterSupervisor {
+  health: Arc<Mutex<RouterHealth>>,
+  shutdown: watch::Sender<bool>,
+}
+
+impl Default for RouterSupervisor {
+  fn default() -> Self {
+    Self {
+      health: Default::default(),
+      shutdown: watch::channel(false).0,
+    }
+  }
+}
+
+impl RouterSupervisor {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// 启动守护任务 factory 每次重启时创建新的路由任务
+  pub fn start<F, Fut>(&self, factory: F)
+  where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+  {
+    let supervisor = self.clone();
+    let mut shutdown = self.shutdown.subscribe();
+    tokio::task::spawn(async move {
+      let mut failures: u32 = 0;
+      loop {
+        if *shutdown.borrow() {
+          break;
+        }
+        supervisor.update(|health| {
+          health.running = true;
+          health.started_at = Some(now_millis());
+        });
+        let started = Instant::now();
+        let mut task = tokio::task::spawn(factory());
+        let failure = tokio::select! {
+          result = &mut task => match result {
+            Ok(()) => "router exited unexpectedly".to_string(),
+            Err(e) if e.is_panic() => panic_message(e.into_panic()),
+            Err(e) => e.to_string(),
+          },
+          _ = shutdown.changed() => {
+            task.abort();
+            break;
+          }
+        };
+        println!("deno ipc router stopped: {}, restarting", failure);
+        if started.elapsed() > STABLE_AFTER {
+          failures = 0;
+        }
+        failures += 1;
+        supervisor.update(|health| {
+          health.running = false;
+          health.restarts += 1;
+          health.last_failure = Some(failure);
+          health.last_failure_at = Some(now_millis());
+        });
+        //连续失败时逐步延长等待 避免忙循环
+        let backoff = (Duration::from_millis(100) * failures.min(50)).min(MAX_BACKOFF);
+        tokio::select! {
+          _ = tokio::time::sleep(backoff) => {}
+          _ = shutdown.changed() => break,
+        }
+      }
+      supervisor.update(|health| health.running = false);
+      println!("deno ipc router shut down");
+    });
+  }
+
+  /// 停止路由任务
+  pub fn shutdown(&self) {
+    self.shutdown.send_replace(true);
+  }
+
+  pub fn health(&self) -> RouterHealth {
+    self.health.lock().unwrap().clone()
+  }
+
+  fn update(&self, f: impl FnOnce(&mut RouterHealth)) {
+    f(&mut self.health.lock().unwrap());
+  }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+  if let Some(message) = payload.downcast_ref::<&str>() {
+    return format!("panicked: {}", message);
+  }
+  if let Some(message) = payload.downcast_ref::<String>() {
+    return format!("panicked: {}", message);
+  }
+  "panicked".to_string()
+}
+
+fn now_millis() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, EventTarget, Manager, ResourceId, Runtime};
+
+use crate::{commands::has_channel_to, scope::glob_match, subscriptions::ChannelSubscriptions};
+
+/// deno -> 插件 按目标发往窗口 {event, payload, target}
+pub const EMIT_EVENT: &str = "deno:emit";
+/// 插件 -> deno 目标无法投递时通知发送的 worker {event, target, message}
+pub const EMIT_ERROR_EVENT: &str = "deno:emit-error";
+
+/// deno 发往窗口的目标 与 tauri EventTarget 对应
+/// labels/pattern/allExcept/channels 展开为多个 webview window
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum WindowTarget {
+  /// 广播给所有监听者
+  Any,
+  /// 只发给 app 级别的监听者
+  App,
+  Window {
+    label: String,
+  },
+  Webview {
+    label: String,
+  },
+  WebviewWindow {
+    label: String,
+  },
+  /// 多个 label 每个都必须存在
+  Labels {
+    labels: Vec<String>,
+  },
+  /// label 通配 `*` `?` 至少匹配一个窗口
+  Pattern {
+    pattern: String,
+  },
+  /// 除指定 label 外的所有窗口 通常用于排除发送方
+  AllExcept {
+    labels: Vec<String>,
+  },
+  /// 持有当前 worker 通道的窗口
+  Channels,
+  /// 回复消息来源 可直接传入 $sender, rid 不为空时只投递到该通道
+  Sender {
+    webview: String,
+    rid: Option<ResourceId>,
+  },
+}
+
+impl WindowTarget {
+  /// 兼容 postMessage({key}) 的写法 key 为空时广播, 否则为窗口 label
+  pub fn from_key(key: &str) -> Self {
+    if key.is_empty() {
+      WindowTarget::Any
+    } else {
+      WindowTarget::WebviewWindow { label: key.to_string() }
+    }
+  }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EmitMessage {
+  pub event: String,
+  #[serde(default)]
+  pub payload: serde_json::Value,
+  pub target: WindowTarget,
+}
+
+/// 按目标发送 worker 发出的事件
+/// 目标窗口中 listen_on 了该事件的通道经通道投递, 其余窗口以 tauri 事件发送, 同一窗口不会重复收到
+/// 指定的 label 不存在或没有匹配的窗口时返回 TargetNotFound, 不会退化为广播
+/// rust 的宿主订阅不区分目标, 包括 App 目标及 TargetNotFound 的消息都会收到
+pub fn emit<R: Runtime>(app: &AppHandle<R>, channels: &ChannelSubscriptions, worker: &str, target: &WindowTarget, event: &str, payload: serde_json::Value) -> crate::Result<()> {
+  channels.deliver_hosts(worker, event, &payload);
+  let windows = app.webview_windows();
+  let require = |label: &str| {
+    if windows.contains_key(label) {
+      Ok(())
+    } else {
+      Err(crate::Error::TargetNotFound(format!("no window labeled `{}`", label)))
+    }
+  };
+  //子 webview 和多 webview 窗口不在 webview_windows 中, 分别在 webviews 和 windows 中查找
+  let require_webview = |label: &str| {
+    if app.webviews().contains_key(label) {
+      Ok(())
+    } else {
+      Err(crate::Error::TargetNotFound(format!("no webview labeled `{}`", label)))
+    }
+  };
+  let require_window = |label: &str| {
+    if app.windows().contains_key(label) {
+      Ok(())
+    } else {
+      Err(crate::Error::TargetNotFound(format!("no window labeled `{}`", label)))
+    }
+  };
+  let labeled = |label: &String| (label.clone(), EventTarget::webview_window(label));
+  let targets: Vec<(String, EventTarget)> = match target {
+    WindowTarget::Any => {
+      //没有通道订阅时直接广播; 否则 app 级别的监听者和没有经通道收到的窗口仍以事件发送
+      let delivered = channels.deliver(worker, event, &payload, |_, _| true);
+      if delivered.is_empty() {
+        app.emit(event, payload)?;
+        return Ok(());
+      }
+      app.emit_to(EventTarget::App, event, payload.clone())?;
+      for label in windows.keys().filter(|label| !delivered.contains(*label)) {
+        app.emit_to(EventTarget::webview_window(label), event, payload.clone())?;
+      }
+      return Ok(());
+    }
+    WindowTarget::App => return Ok(app.emit_to(EventTarget::App, event, payload)?),
+    WindowTarget::Window { label } => {
+      require_window(label)?;
+      vec![(label.clone(), EventTarget::window(label))]
+    }
+    WindowTarget::Webview { label } => {
+      require_webview(label)?;
+      vec![(label.clone(), EventTarget::webview(label))]
+    }
+    WindowTarget::WebviewWindow { label } => {
+      require(label)?;
+      vec![labeled(label)]
+    }
+    WindowTarget::Labels { labels } => {
+      for label in labels {
+        require(label)?;
+      }
+      labels.iter().map(labeled).collect()
+    }
+    WindowTarget::Pattern { pattern } => {
+      let matched: Vec<(String, EventTarget)> = windows.keys().filter(|label| glob_match(pattern, label)).map(labeled).collect();
+      if matched.is_empty() {
+        return Err(crate::Error::TargetNotFound(format!("no window matches `{}`", pattern)));
+      }
+      matched
+    }
+    WindowTarget::AllExcept { labels } => windows.keys().filter(|label| !labels.contains(label)).map(labeled).collect(),
+    WindowTarget::Channels => windows.iter().filter(|(_, window)| has_channel_to(window, worker)).map(|(label, _)| labeled(label)).collect(),
+    WindowTarget::Sender { webview, rid } => {
+      require_webview(webview)?;
+      let delivered = channels.deliver(worker, event, &payload, |label, channel| label == webview && rid.map_or(true, |rid| rid == channel));
+      if delivered.is_empty() {
+        app.emit_to(EventTarget::webview(webview), event, payload)?;
+      }
+      return Ok(());
+    }
+  };
+  let delivered = channels.deliver(worker, event, &payload, |label, _| targets.iter().any(|(target_label, _)| target_label == label));
+  for (label, target) in targets {
+    if !delivered.contains(&label) {
+      app.emit_to(target, event, payload.clone())?;
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, LogicalSize, Manager, Runtime, WebviewUrl, WebviewWindowBuilder};
+
+use crate::scope::glob_match;
+
+/// deno -> 插件 窗口操作请求 {id, op, ...}
+pub const WINDOW_EVENT: &str = "deno:window";
+/// 插件 -> deno 窗口操作结果 {id, result, error}
+pub const WINDOW_REPLY_EVENT: &str = "deno:window-reply";
+
+/// worker 可申请的窗口权限 与 WindowOp 一一对应
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum WindowPermission {
+  Create,
+  Show,
+  Hide,
+  Focus,
+  Resize,
+  Close,
+  List,
+}
+
+/// 授权 worker 和 windows 为 label 通配, 未授权的操作一律拒绝
+#[derive(Debug, Clone)]
+pub struct WindowGrant {
+  pub worker: String,
+  pub windows: String,
+  pub permissions: Vec<WindowPermission>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum WindowOp {
+  /// url 以 http(s) 开头时为外部地址 否则为应用内路径
+  Create {
+    label: String,
+    url: Option<String>,
+    title: Option<String>,
+    width: Option<f64>,
+    height: Option<f64>,
+    visible: Option<bool>,
+  },
+  Show {
+    label: String,
+  },
+  Hide {
+    label: String,
+  },
+  Focus {
+    label: String,
+  },
+  Resize {
+    label: String,
+    width: f64,
+    height: f64,
+  },
+  Close {
+    label: String,
+  },
+  List,
+}
+
+impl WindowOp {
+  fn permission(&self) -> WindowPermission {
+    match self {
+      WindowOp::Create { .. } => WindowPermission::Create,
+      WindowOp::Show { .. } => WindowPermission::Show,
+      WindowOp::Hide { .. } => WindowPermission::Hide,
+      WindowOp::Focus { .. } => WindowPermission::Focus,
+      WindowOp::Resize { .. } => WindowPermission::Resize,
+      WindowOp::Close { .. } => WindowPermission::Close,
+      WindowOp::List => WindowPermission::List,
+    }
+  }
+
+  fn label(&self) -> Option<&str> {
+    match self {
+      WindowOp::Create { label, .. } | WindowOp::Show { label } | WindowOp::Hide { label } | WindowOp::Focus { label } | WindowOp::Resize { label, .. } | WindowOp::Close { label } => Some(label),
+      WindowOp::List => None,
+    }
+  }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WindowRequest {
+  pub id: String,
+  #[serde(flatten)]
+  pub op: WindowOp,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WindowReply {
+  pub id: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub result: Option<serde_json::Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<crate::Error>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowInfo {
+  pub label: String,
+  pub title: Option<String>,
+  pub url: Option<String>,
+  pub visible: bool,
+  pub focused: bool,
+}
+
+/// worker 的窗口操作
+#[derive(Clone, Default)]
+pub struct WindowManager {
+  grants: std::sync::Arc<Vec<WindowGrant>>,
+}
+
+impl WindowManager {
+  pub fn new(grants: Vec<WindowGrant>) -> Self {
+    Self { grants: std::sync::Arc::new(grants) }
+  }
+
+  /// list 只校验 worker, 其余操作还要求 label 匹配授权的 windows
+  pub fn check(&self, worker: &str, op: &WindowOp) -> crate::Result<()> {
+    let permission = op.permission();
+    let allowed = self
+      .grants
+      .iter()
+      .any(|grant| glob_match(&grant.worker, worker) && grant.permissions.contains(&permission) && op.label().map_or(true, |label| glob_match(&grant.windows, label)));
+    if allowed {
+      return Ok(());
+    }
+    Err(crate::Error::PermissionDenied(match op.label() {
+      Some(label) => format!("worker `{}` {:?} window `{}`", worker, permission, label),
+      None => format!("worker `{}` {:?} windows", worker, permission),
+    }))
+  }
+
+  pub fn handle<R: Runtime>(&self, app: &AppHandle<R>, worker: &str, request: WindowRequest) -> WindowReply {
+    let WindowRequest { id, op } = request;
+    match self.check(worker, &op).and_then(|_| apply(app, op)) {
+      Ok(result) => WindowReply { id, result: Some(result), error: None },
+      Err(error) => WindowReply { id, result: None, error: Some(error) },
+    }
+  }
+}
+
+fn window<R: Runtime>(app: &AppHandle<R>, label: &str) -> crate::Result<tauri::WebviewWindow<R>> {
+  app.get_webview_window(label).ok_or_else(|| crate::Error::TargetNotFound(format!("no window labeled `{}`", label)))
+}
+
+fn apply<R: Runtime>(app: &AppHandle<R>, op: WindowOp) -> crate::Result<serde_json::Value> {
+  match op {
+    WindowOp::Create { label, url, title, width, height, visible } => {
+      if app.get_webview_window(&label).is_some() {
+        return Err(crate::Error::Handler(format!("window `{}` already exists", label)));
+      }
+      let url = match url {
+        Some(url) if url.starts_with("http://") || url.starts_with("https://") => WebviewUrl::External(url.parse().map_err(|e| crate::Error::Handler(format!("invalid url `{}`: {}", url, e)))?),
+        Some(url) => WebviewUrl::App(url.into()),
+        None => WebviewUrl::default(),
+      };
+      let mut builder = WebviewWindowBuilder::new(app, &label, url);
+      if let Some(title) = title {
+        builder = builder.title(title);
+      }
+      if let (Some(width), Some(height)) = (width, height) {
+        builder = builder.inner_size(width, height);
+      }
+      if let Some(visible) = visible {
+        builder = builder.visible(visible);
+      }
+      builder.build()?;
+    }
+    WindowOp::Show { label } => window(app, &label)?.show()?,
+    WindowOp::Hide { label } => window(app, &label)?.hide()?,
+    WindowOp::Focus { label } => window(app, &label)?.set_focus()?,
+    WindowOp::Resize { label, width, height } => window(app, &label)?.set_size(LogicalSize::new(width, height))?,
+    WindowOp::Close { label } => window(app, &label)?.close()?,
+    WindowOp::List => {
+      let windows: Vec<WindowInfo> = app
+        .webview_windows()
+        .into_iter()
+        .map(|(label, window)| WindowInfo {
+          label,
+          title: window.title().ok(),
+          url: window.url().ok().map(|url| url.to_string()),
+          visible: window.is_visible().unwrap_or_default(),
+          focused: window.is_focused().unwrap_or_default(),
+        })
+        .collect();
+      return Ok(serde_json::to_value(windows)?);
+    }
+  }
+  Ok(serde_json::Value::Null)
+}
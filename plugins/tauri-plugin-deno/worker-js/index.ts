@@ -0,0 +1,452 @@
+// deno worker 端辅助函数 与 guest-js 对应
+// 底层使用 Deno.IpcBroadcastChannel: 构造参数为监听的事件名, postMessage({key,name,message}) 发往窗口
+
+//插件约定的事件名 与 src 下对应模块保持一致
+export const INVOKE_EVENT = "deno:invoke";
+export const REPLY_EVENT = "deno:reply";
+export const STREAM_EVENT = "deno:stream";
+export const STREAM_CANCEL_EVENT = "deno:stream-cancel";
+export const STREAM_CHUNK_EVENT = "deno:stream-chunk";
+export const PUBLISH_EVENT = "deno:publish";
+export const SUBSCRIBE_EVENT = "deno:subscribe";
+export const UNSUBSCRIBE_EVENT = "deno:unsubscribe";
+export const TOPIC_EVENT = "deno:topic";
+export const EMIT_EVENT = "deno:emit";
+export const EMIT_ERROR_EVENT = "deno:emit-error";
+export const HOST_CALL_EVENT = "deno:host-call";
+export const HOST_REPLY_EVENT = "deno:host-reply";
+export const WINDOW_EVENT = "deno:window";
+export const WINDOW_REPLY_EVENT = "deno:window-reply";
+export const LIFECYCLE_EVENT = "deno:lifecycle";
+export const CLOSE_REQUESTED_METHOD = "deno:close-requested";
+export const FETCH_EVENT = "deno:fetch";
+export const FETCH_RESPONSE_EVENT = "deno:fetch-response";
+export const KV_EVENT = "deno:kv";
+export const KV_REPLY_EVENT = "deno:kv-reply";
+export const KV_CHANGE_EVENT = "deno:kv-change";
+//等待宿主应答的默认超时(毫秒) 与 src/rpc.rs DEFAULT_TIMEOUT_MS 相同
+export const DEFAULT_TIMEOUT = 30_000;
+
+//当前 worker 的 key 由插件作为脚本参数传入
+export function workerKey(): string {
+  return Deno.args[0] ?? "main";
+}
+
+export type Handler = (args: any) => any | Promise<any>;
+export type StreamHandler = (args: any) => AsyncIterable<any> | Iterable<any>;
+
+interface InvokeRequest {
+  id: string;
+  method: string;
+  args: any;
+}
+
+//webview 发来的消息来源 与 src/envelope.rs 保持一致
+export interface MessageSender {
+  webview: string;
+  window: string;
+  rid: number | null;
+  origin: string | null;
+  timestamp: number;
+}
+//拆开 {"$sender","$content"} 外层 只有 webview 以 withSender 创建的通道才带外层, 其余消息 sender 为 null
+export function unwrapEnvelope(value: any): { content: any; sender: MessageSender | null } {
+  if (value && typeof value === "object" && "$sender" in value && "$content" in value) {
+    return { content: value.$content, sender: value.$sender };
+  }
+  return { content: value, sender: null };
+}
+
+//二进制数据经 deno_ipc 时的格式 {"$binary": number[]} 与 src/binary.rs 保持一致 不做 base64 编码
+export function encodeBinary(data: Uint8Array | ArrayBuffer) {
+  const bytes = data instanceof Uint8Array ? data : new Uint8Array(data);
+  return { $binary: Array.from(bytes) };
+}
+export function decodeBinary(value: any): Uint8Array | null {
+  if (value && typeof value === "object" && Array.isArray(value.$binary)) {
+    return Uint8Array.from(value.$binary);
+  }
+  return null;
+}
+//Uint8Array/ArrayBuffer 自动转为约定格式
+function encodeValue(value: any) {
+  if (value instanceof Uint8Array || value instanceof ArrayBuffer) {
+    return encodeBinary(value);
+  }
+  return value ?? null;
+}
+
+const handlers: Map<string, Handler> = new Map();
+let invokeChannel: any = null;
+
+//向插件发送消息 key为空时由插件自行处理
+export function post(key: string, name: string, message: any) {
+  ensureInvokeChannel().postMessage({ key, name, message: encodeValue(message) });
+}
+
+//监听事件 二进制消息以 Uint8Array 回调 sender 为发送消息的 webview(通道需开启 withSender) 可用 replyTo 回复
+export function listen(name: string, fn: (data: any, sender: MessageSender | null) => void) {
+  //@ts-ignore
+  const channel = new Deno.IpcBroadcastChannel(name);
+  channel.onmessage = ({ data }: MessageEvent) => {
+    const { content, sender } = unwrapEnvelope(data);
+    fn(decodeBinary(content) ?? content, sender);
+  };
+  return () => channel.close();
+}
+
+function ensureInvokeChannel() {
+  if (invokeChannel) return invokeChannel;
+  //@ts-ignore
+  invokeChannel = new Deno.IpcBroadcastChannel(INVOKE_EVENT);
+  invokeChannel.onmessage = async ({ data }: MessageEvent) => {
+    const { id, method, args } = data as InvokeRequest;
+    const fn = handlers.get(method);
+    let reply: any;
+    try {
+      if (!fn) throw new Error(`method not found: ${method}`);
+      reply = { id, result: encodeValue(await fn(args)) };
+    } catch (e: any) {
+      reply = { id, error: e?.message ?? String(e) };
+    }
+    invokeChannel.postMessage({ key: "", name: REPLY_EVENT, message: reply });
+  };
+  return invokeChannel;
+}
+
+//注册可被 webview invokeDeno 调用的方法
+export function handle(method: string, fn: Handler) {
+  ensureInvokeChannel();
+  handlers.set(method, fn);
+  return () => handlers.delete(method);
+}
+
+const streamHandlers: Map<string, StreamHandler> = new Map();
+const cancelled: Set<string> = new Set();
+let streamChannel: any = null;
+let cancelChannel: any = null;
+
+function ensureStreamChannel() {
+  if (streamChannel) return streamChannel;
+  //@ts-ignore
+  cancelChannel = new Deno.IpcBroadcastChannel(STREAM_CANCEL_EVENT);
+  cancelChannel.onmessage = ({ data }: MessageEvent) => {
+    cancelled.add(data.id);
+  };
+  //@ts-ignore
+  streamChannel = new Deno.IpcBroadcastChannel(STREAM_EVENT);
+  streamChannel.onmessage = async ({ data }: MessageEvent) => {
+    const { id, method, args } = data as InvokeRequest;
+    const send = (frame: any) =>
+      streamChannel.postMessage({ key: "", name: STREAM_CHUNK_EVENT, message: { id, ...frame } });
+    try {
+      const fn = streamHandlers.get(method);
+      if (!fn) throw new Error(`method not found: ${method}`);
+      for await (const data of fn(args)) {
+        if (cancelled.has(id)) break;
+        send({ kind: "chunk", data: encodeValue(data) });
+      }
+      send({ kind: "end" });
+    } catch (e: any) {
+      send({ kind: "error", message: e?.message ?? String(e) });
+    } finally {
+      cancelled.delete(id);
+    }
+  };
+  return streamChannel;
+}
+
+//注册可被 webview streamDeno 调用的流式方法 处理函数为(异步)生成器
+export function handleStream(method: string, fn: StreamHandler) {
+  ensureStreamChannel();
+  streamHandlers.set(method, fn);
+  return () => streamHandlers.delete(method);
+}
+
+const topicHandlers: Map<string, (payload: any, topic: string) => void> = new Map();
+let topicChannel: any = null;
+
+function ensureTopicChannel() {
+  if (topicChannel) return topicChannel;
+  //@ts-ignore
+  topicChannel = new Deno.IpcBroadcastChannel(TOPIC_EVENT);
+  topicChannel.onmessage = ({ data }: MessageEvent) => {
+    const { id, topic, payload } = data;
+    topicHandlers.get(id)?.(decodeBinary(payload) ?? payload, topic);
+  };
+  return topicChannel;
+}
+
+//发布主题消息 webview 和其他 worker 的订阅者都能收到
+export function publish(topic: string, payload: any, retain = false) {
+  post("", PUBLISH_EVENT, { topic, payload: encodeValue(payload), retain });
+}
+
+//订阅主题 `*` 匹配一级 `**` 匹配任意级 返回取消订阅函数
+export function subscribe(pattern: string, fn: (payload: any, topic: string) => void) {
+  ensureTopicChannel();
+  const id = crypto.randomUUID();
+  topicHandlers.set(id, fn);
+  post("", SUBSCRIBE_EVENT, { id, pattern });
+  return () => {
+    topicHandlers.delete(id);
+    post("", UNSUBSCRIBE_EVENT, { id });
+  };
+}
+
+//发往窗口的目标 与 src/target.rs 保持一致
+export type WindowTarget =
+  | { kind: "any" }
+  | { kind: "app" }
+  | { kind: "window"; label: string }
+  | { kind: "webview"; label: string }
+  | { kind: "webviewWindow"; label: string }
+  | { kind: "labels"; labels: string[] }
+  | { kind: "pattern"; pattern: string }
+  | { kind: "allExcept"; labels: string[] }
+  | { kind: "channels" }
+  | { kind: "sender"; webview: string; rid?: number | null };
+
+//按目标向窗口发送事件 目标不存在时不会广播 错误通过 onEmitError 回调
+export function emit(event: string, payload: any, target: WindowTarget = { kind: "any" }) {
+  post("", EMIT_EVENT, { event, payload: encodeValue(payload), target });
+}
+
+//回复消息来源窗口 发送时使用的通道 listen_on 了该事件时经通道投递
+export function replyTo(sender: MessageSender, event: string, payload: any) {
+  emit(event, payload, { kind: "sender", webview: sender.webview, rid: sender.rid });
+}
+
+//监听 emit 失败 error 与 webview 端 DenoError 结构相同
+export function onEmitError(fn: (error: { event: string; target: WindowTarget; error: { code: string; message: string; details: any } }) => void) {
+  return listen(EMIT_ERROR_EVENT, fn);
+}
+
+const hostCalls: Map<string, { resolve: (value: any) => void; reject: (reason: any) => void }> = new Map();
+let hostChannel: any = null;
+
+function ensureHostChannel() {
+  if (hostChannel) return hostChannel;
+  //@ts-ignore
+  hostChannel = new Deno.IpcBroadcastChannel(HOST_REPLY_EVENT);
+  hostChannel.onmessage = ({ data }: MessageEvent) => {
+    const { id, result, error } = data;
+    const pending = hostCalls.get(id);
+    if (!pending) return;
+    hostCalls.delete(id);
+    if (error !== undefined) pending.reject(new Error(error));
+    else pending.resolve(decodeBinary(result) ?? result);
+  };
+  return hostChannel;
+}
+
+//调用宿主通过 Builder::command 注册的 rust 命令 timeout 单位毫秒 默认 DEFAULT_TIMEOUT, 传 0 时一直等待
+export function callHost<T = any>(name: string, args?: any, timeout: number = DEFAULT_TIMEOUT): Promise<T> {
+  ensureHostChannel();
+  const id = crypto.randomUUID();
+  return new Promise<T>((resolve, reject) => {
+    hostCalls.set(id, { resolve, reject });
+    if (timeout) {
+      setTimeout(() => {
+        if (hostCalls.delete(id)) reject(new Error(`host command ${name} timed out after ${timeout}ms`));
+      }, timeout);
+    }
+    post("", HOST_CALL_EVENT, { id, name, args: encodeValue(args) });
+  });
+}
+
+//应答超时时以 {code:"timeout"} reject 与插件错误格式一致
+function expireAfter(calls: Map<string, any>, id: string, what: string, timeout: number) {
+  setTimeout(() => {
+    const pending = calls.get(id);
+    if (!pending) return;
+    calls.delete(id);
+    pending.reject({ code: "timeout", message: `${what} timed out after ${timeout}ms`, details: { timeout } });
+  }, timeout);
+}
+
+//窗口操作 需要宿主通过 Builder::window_permission 授权 失败时以 {code,message,details} reject
+export type WindowOp =
+  | { op: "create"; label: string; url?: string; title?: string; width?: number; height?: number; visible?: boolean }
+  | { op: "show"; label: string }
+  | { op: "hide"; label: string }
+  | { op: "focus"; label: string }
+  | { op: "resize"; label: string; width: number; height: number }
+  | { op: "close"; label: string }
+  | { op: "list" };
+export interface WindowInfo {
+  label: string;
+  title: string | null;
+  url: string | null;
+  visible: boolean;
+  focused: boolean;
+}
+
+const windowCalls: Map<string, { resolve: (value: any) => void; reject: (reason: any) => void }> = new Map();
+let windowChannel: any = null;
+
+function ensureWindowChannel() {
+  if (windowChannel) return windowChannel;
+  //@ts-ignore
+  windowChannel = new Deno.IpcBroadcastChannel(WINDOW_REPLY_EVENT);
+  windowChannel.onmessage = ({ data }: MessageEvent) => {
+    const { id, result, error } = data;
+    const pending = windowCalls.get(id);
+    if (!pending) return;
+    windowCalls.delete(id);
+    if (error !== undefined) pending.reject(error);
+    else pending.resolve(result);
+  };
+  return windowChannel;
+}
+
+export function windowOp<T = null>(op: WindowOp): Promise<T> {
+  ensureWindowChannel();
+  const id = crypto.randomUUID();
+  return new Promise<T>((resolve, reject) => {
+    windowCalls.set(id, { resolve, reject });
+    expireAfter(windowCalls, id, `window ${op.op}`, DEFAULT_TIMEOUT);
+    post("", WINDOW_EVENT, { id, ...op });
+  });
+}
+
+export const windows = {
+  create: (label: string, options: { url?: string; title?: string; width?: number; height?: number; visible?: boolean } = {}) =>
+    windowOp({ op: "create", label, ...options }),
+  show: (label: string) => windowOp({ op: "show", label }),
+  hide: (label: string) => windowOp({ op: "hide", label }),
+  focus: (label: string) => windowOp({ op: "focus", label }),
+  resize: (label: string, width: number, height: number) => windowOp({ op: "resize", label, width, height }),
+  close: (label: string) => windowOp({ op: "close", label }),
+  list: () => windowOp<WindowInfo[]>({ op: "list" }),
+};
+
+//窗口及应用生命周期 由插件转发 visibility 只在可见窗口从有到无或从无到有时发送
+//没有应用挂起/恢复事件 (桌面端无法可靠感知), 前后台切换以 visibility 判断
+export type LifecycleEvent =
+  | { kind: "windowCreated"; label: string }
+  | { kind: "windowDestroyed"; label: string }
+  | { kind: "windowFocused"; label: string }
+  | { kind: "windowBlurred"; label: string }
+  | { kind: "closeRequested"; label: string }
+  | { kind: "themeChanged"; label: string; theme: "light" | "dark" | "unknown" }
+  | { kind: "visibility"; visible: boolean }
+  | { kind: "appReady" }
+  | { kind: "exitRequested"; code: number | null }
+  | { kind: "exit" };
+
+//订阅生命周期事件 kinds 为空时接收全部
+export function onLifecycle(fn: (event: LifecycleEvent) => void, kinds?: LifecycleEvent["kind"][]) {
+  return listen(LIFECYCLE_EVENT, (event: LifecycleEvent) => {
+    if (!kinds || kinds.includes(event.kind)) fn(event);
+  });
+}
+
+//所有窗口隐藏或最小化时 visible 为 false, 可据此暂停轮询
+export function onVisibilityChange(fn: (visible: boolean) => void) {
+  return onLifecycle((event) => event.kind === "visibility" && fn(event.visible), ["visibility"]);
+}
+
+//窗口关闭前确认 需在 Builder::close_guard 中指定本 worker, 返回 false 阻止关闭 超时未返回时照常关闭
+export function onCloseRequested(fn: (label: string) => boolean | Promise<boolean>) {
+  return handle(CLOSE_REQUESTED_METHOD, async ({ label }: { label: string }) => (await fn(label)) !== false);
+}
+
+export type FetchHandler = (request: Request) => Response | Promise<Response>;
+let fetchHandler: FetchHandler | null = null;
+let fetchChannel: any = null;
+
+function ensureFetchChannel() {
+  if (fetchChannel) return fetchChannel;
+  //@ts-ignore
+  fetchChannel = new Deno.IpcBroadcastChannel(FETCH_EVENT);
+  fetchChannel.onmessage = async ({ data }: MessageEvent) => {
+    const { id, method, url, headers, body } = data;
+    const send = (frame: any) =>
+      fetchChannel.postMessage({ key: "", name: FETCH_RESPONSE_EVENT, message: { id, ...frame } });
+    try {
+      if (!fetchHandler) throw new Error("no fetch handler, call serve first");
+      const request = new Request(url, { method, headers, body: decodeBinary(body) });
+      const response = await fetchHandler(request);
+      send({ kind: "head", status: response.status, headers: Array.from(response.headers.entries()) });
+      if (response.body) {
+        for await (const chunk of response.body) {
+          send({ kind: "chunk", data: encodeBinary(chunk) });
+        }
+      }
+      send({ kind: "end" });
+    } catch (e: any) {
+      send({ kind: "error", message: e?.message ?? String(e) });
+    }
+  };
+  return fetchChannel;
+}
+
+//处理 webview 对 deno://localhost/<worker>/<path> 的请求 request.url 中已去掉 worker 段
+//windows 上地址为 http://deno.localhost/<worker>/<path>
+//响应体由插件收齐后一次性交给 webview, 不支持 SSE、长轮询等持续输出的响应; 两个分片间隔超过 fetch_timeout 时 webview 收到 504
+export function serve(fn: FetchHandler) {
+  ensureFetchChannel();
+  fetchHandler = fn;
+  return () => {
+    if (fetchHandler === fn) fetchHandler = null;
+  };
+}
+
+//宿主以 Builder::ops 注册的 op name 为注册时的扩展名 deno 2 不再向脚本暴露 Deno.core
+//异步 op 返回 Promise, 同步 op 直接返回结果
+export function hostOps(name: string): Record<string, (...args: any[]) => any> {
+  const ops = (globalThis as any)[Symbol.for("tauri-plugin-deno.ops")]?.[name];
+  if (!ops) throw new Error(`host ops not registered: ${name}`);
+  return ops;
+}
+
+//键值存储 与 webview 共用, 修改后所有窗口和 worker 都会收到 KvChange
+export interface KvEntry {
+  key: string;
+  value: any;
+}
+export interface KvChange {
+  key: string;
+  value: any | null; //删除时为 null, 也可能是写入的 null
+  deleted: boolean; //是否为删除
+  source: string; //发起修改的窗口 label 或 worker key
+}
+const kvCalls: Map<string, { resolve: (value: any) => void; reject: (reason: any) => void }> = new Map();
+let kvChannel: any = null;
+
+function ensureKvChannel() {
+  if (kvChannel) return kvChannel;
+  //@ts-ignore
+  kvChannel = new Deno.IpcBroadcastChannel(KV_REPLY_EVENT);
+  kvChannel.onmessage = ({ data }: MessageEvent) => {
+    const { id, result, error } = data;
+    const pending = kvCalls.get(id);
+    if (!pending) return;
+    kvCalls.delete(id);
+    if (error !== undefined) pending.reject(error);
+    else pending.resolve(result);
+  };
+  return kvChannel;
+}
+
+function kvOp<T>(op: any): Promise<T> {
+  ensureKvChannel();
+  const id = crypto.randomUUID();
+  return new Promise<T>((resolve, reject) => {
+    kvCalls.set(id, { resolve, reject });
+    expireAfter(kvCalls, id, `kv ${op.op}`, DEFAULT_TIMEOUT);
+    post("", KV_EVENT, { id, ...op });
+  });
+}
+
+export const kv = {
+  get: <T = any>(key: string) => kvOp<T | null>({ op: "get", key }),
+  set: (key: string, value: any) => kvOp<null>({ op: "set", key, value }),
+  delete: (key: string) => kvOp<boolean>({ op: "delete", key }),
+  list: (prefix?: string) => kvOp<KvEntry[]>({ op: "list", prefix }),
+};
+
+export function onKvChange(fn: (change: KvChange) => void) {
+  return listen(KV_CHANGE_EVENT, fn);
+}
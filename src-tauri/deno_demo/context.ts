@@ -3,9 +3,10 @@
 import { Args } from "./types.ts";
 import config from "./deno.json" with  { type: "json" };
 import { buildRouter } from "./core.ts";
-import { Application } from "jsr:@oak/oak/application";
-import { Router } from "jsr:@oak/oak/router";
-import { cors, type CorsOptions } from "jsr:@momiji/cors";
+import { serve, unwrapEnvelope } from "tauri-plugin-deno/worker";
+
+//允许读取 deno:// 响应的页面来源 开发服务器及各平台的应用页面
+const ALLOWED_ORIGINS = ["http://localhost:8080", "tauri://localhost", "http://tauri.localhost", "https://tauri.localhost"];
 
 class Context {
   service: { [key: string]: any } = {};
@@ -69,7 +70,7 @@ class Context {
     (async () => {
       await ctx.loaderAndBuilder();
        ctx.startIpcServer();
-       ctx.startHttpServer();
+       ctx.startFetchServer();
     })();
    
   }
@@ -78,7 +79,9 @@ class Context {
     const { controller } = ctx;
     //@ts-ignore
     let ipcBroadcastChannel = new Deno.IpcBroadcastChannel("testIpc");
-    ipcBroadcastChannel.onmessage=async ({data:request}: MessageEvent)=>{
+    ipcBroadcastChannel.onmessage=async ({data}: MessageEvent)=>{
+      //以 withSender 创建的通道发来的消息外层带有来源 {$sender,$content} 其余消息没有外层
+      const { content: request, sender } = unwrapEnvelope(data);
       let response = {status:200,message:"success",body:""};
       try {
         if(request.url){
@@ -98,68 +101,41 @@ class Context {
       }catch (e:any) {
         response={status:500,message:e.message,body:""};
       }finally {
-        ipcBroadcastChannel.postMessage({key:"main",name:"test",message:response});
-        ipcBroadcastChannel.postMessage({key:"main",name:"test1",message:response});
+        //回复发送请求的窗口
+        const key = sender?.webview ?? "main";
+        ipcBroadcastChannel.postMessage({key,name:"test",message:response});
+        ipcBroadcastChannel.postMessage({key,name:"test1",message:response});
       }   
     }
-    //main：消息发送到主窗口的(如果为空 则发送到所有的窗口)  testIpc:事件名称(如果main窗口没有监听的话 是收不到的)
+    //key：消息发送到指定 label 的窗口(如果为空 则发送到所有的窗口)  testIpc:事件名称(如果窗口没有监听的话 是收不到的)
     
   }
-  startHttpServer(){
-    // deno-lint-ignore no-this-alias
-    const self = this;
-    const router = new Router();
-    for (let [key, value] of this.routers) {
-      switch (value.method) {
-        case "POST":{
-          console.log(value.className,key);
-          router.post(key, async (ctx) => {
-            let body = await ctx.request.body.json();
-           let responseBody = await self.controller[value.className][value.key](body);
-           ctx.response.body = responseBody;
-          });
-          break;
-        }
-        case "GET":{
-          router.get(key, async (ctx) => {
-            let responseBody = await self.controller[value.className][value.key](ctx.params);
-            ctx.response.body = responseBody;
-           });
-          break;
-        }
-        
-      }
-    }
-    router.get("/wss", (ctx) => {
-      if (!ctx.isUpgradable) {
-        ctx.throw(501);
+  //处理 webview 对 deno://localhost/main/<path> 的请求 不再占用本地端口
+  //页面与协议不同源, 插件不加默认跨域头 由这里按来源放行
+  startFetchServer(){
+    const ctx = this;
+    const { controller } = ctx;
+    const cors = (request: Request, response: Response) => {
+      const origin = request.headers.get("origin");
+      if (origin && ALLOWED_ORIGINS.includes(origin)) {
+        response.headers.set("Access-Control-Allow-Origin", origin);
+        response.headers.set("Access-Control-Allow-Methods", "GET, POST");
+        response.headers.set("Access-Control-Allow-Headers", "Content-Type");
+        response.headers.set("Vary", "Origin");
       }
-      const ws = ctx.upgrade();
-      ws.onopen = () => {
-        console.log("Connected to client");
-        ws.send("Hello from server!");
-      };
-      ws.onmessage = (m) => {
-        console.log("Got message from client: ", m.data);
-        ws.send(m.data as string);
-        ws.close();
-      };
-      ws.onclose = () => console.log("Disconncted from client");
-    });
-    const corsOptions: CorsOptions = {
-      origin: "http://localhost:8080",
-      allowMethods: ["GET", "POST"],
-      allowHeaders: ["Content-Type"],
-      credentials: true,
-      maxAge: 86400,
+      return response;
     };
-    const app = new Application();
-    //@ts-ignore
-    app.use(cors(corsOptions));
-    app.use(router.routes());
-    app.use(router.allowedMethods());
-    console.log("http://localhost:9999");
-    app.listen({ port: 9999 });
+    serve(async (request: Request) => {
+      if (request.method == "OPTIONS") return cors(request, new Response(null, { status: 204 }));
+      const match = ctx.matchUrl(request.url);
+      if (!match) return cors(request, new Response("notfound", { status: 404 }));
+      if (match.router.method != request.method)
+        return cors(request, new Response("not support " + request.method, { status: 405 }));
+      const target = controller[match.router.className];
+      const args = request.method == "GET" ? match.groups : await request.json();
+      const body = await target[match.router.key].call(target, args);
+      return cors(request, Response.json(body ?? null));
+    });
   }
 }
 
@@ -4,6 +4,12 @@ use getset::{Getters, Setters};
 #[getset(get_mut = "pub", get = "pub", set = "pub")]
 pub struct ServerConfig {
   port: Option<u16>,
+  ///是否在 port 上启动本地网关 只监听 127.0.0.1
+  #[serde(default)]
+  gateway: bool,
+  ///是否监听本地控制 socket 供命令行子命令操作正在运行的实例 默认关闭
+  #[serde(default)]
+  control: bool,
 }
 
 #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize, Clone, Getters, Setters, Default)]
@@ -24,6 +30,9 @@ pub struct ApplicationConfig {
   ///default path "target/logs/"
   log: LogConfig,
   server: ServerConfig,
+  ///JwtToken秘钥 本地网关的 token 由此派生
+  #[serde(default)]
+  jwt_secret: String,
   dev_code_path: String,
   pro_code_path: String,
 }
@@ -1,7 +1,8 @@
-use clap::Parser;
-use serde::Serialize;
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
 /**
 *description:启动参数 --CASSIE_CONFIG XXX 可主动指定启动配置文件
+*带子命令时不启动应用, 经控制 socket 操作正在运行的实例
 *author:cassie-lxd<348040933@qq.com>
 */
 #[derive(Debug, Clone, Parser, Serialize)]
@@ -9,4 +10,32 @@ use serde::Serialize;
 pub struct Opt {
   #[clap(long, env = "config", default_value = "")]
   pub config_path: String,
+  ///控制 socket 路径 为空时为临时目录下的 tauri-desktop.sock
+  #[clap(long, env = "control_socket", default_value = "")]
+  pub control_socket: String,
+  #[clap(subcommand)]
+  pub command: Option<Command>,
+}
+
+///控制命令 以一行 json 发给正在运行的实例
+#[derive(Debug, Clone, Subcommand, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "kebab-case")]
+pub enum Command {
+  ///向 worker 发送事件 例: send --worker main --event reload-cache
+  Send {
+    #[clap(long, default_value = "main")]
+    worker: String,
+    #[clap(long)]
+    event: String,
+    ///json 载荷 不是合法 json 时按字符串发送
+    #[clap(long)]
+    payload: Option<String>,
+  },
+  ///列出正在运行的 worker
+  ListWorkers,
+  ///以原来的模块重新启动 worker
+  RestartWorker {
+    #[clap(long)]
+    worker: String,
+  },
 }
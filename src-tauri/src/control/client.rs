@@ -0,0 +1,29 @@
+use std::path::Path;
+
+use crate::config::option::Command;
+
+///发送一条控制命令并打印结果 实例返回错误时以 Err 退出
+#[cfg(unix)]
+pub async fn run_command(path: &Path, command: Command) -> anyhow::Result<()> {
+  use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+  use tokio::net::UnixStream;
+
+  let stream = UnixStream::connect(path).await.map_err(|e| anyhow::anyhow!("failed to connect {}: {}, is the app running?", path.display(), e))?;
+  let (reader, mut writer) = stream.into_split();
+  let mut line = serde_json::to_string(&command)?;
+  line.push('\n');
+  writer.write_all(line.as_bytes()).await?;
+  let response = BufReader::new(reader).lines().next_line().await?.ok_or_else(|| anyhow::anyhow!("connection closed without a response"))?;
+  let response: serde_json::Value = serde_json::from_str(&response)?;
+  if response["ok"].as_bool().unwrap_or_default() {
+    println!("{}", serde_json::to_string_pretty(&response["result"])?);
+    Ok(())
+  } else {
+    anyhow::bail!("{}", response["error"])
+  }
+}
+
+#[cfg(not(unix))]
+pub async fn run_command(_path: &Path, _command: Command) -> anyhow::Result<()> {
+  anyhow::bail!("control socket is only supported on unix")
+}
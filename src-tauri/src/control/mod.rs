@@ -0,0 +1,19 @@
+/**
+ *model: control 本地控制 socket
+ *description: 运行中的实例监听 unix socket, 子命令作为客户端连接后按行收发 json
+ */
+pub mod client;
+pub mod server;
+
+use std::path::PathBuf;
+
+pub use client::run_command;
+pub use server::spawn;
+
+///控制 socket 路径 未指定时为临时目录下的 tauri-desktop.sock
+pub fn socket_path(path: &str) -> PathBuf {
+  match path {
+    "" => std::env::temp_dir().join("tauri-desktop.sock"),
+    path => PathBuf::from(path),
+  }
+}
@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_deno::DenoManager;
+
+use crate::config::option::Command;
+
+///监听控制 socket 权限为仅当前用户可读写
+///路径上已有可连接的 socket 说明另一个实例正在监听, 此时不启动; 无法连接的旧文件视为残留并替换
+#[cfg(unix)]
+pub fn spawn<R: Runtime>(app: AppHandle<R>, path: PathBuf) {
+  use std::os::unix::fs::PermissionsExt;
+  use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+  use tokio::net::{UnixListener, UnixStream};
+
+  tauri::async_runtime::spawn(async move {
+    if path.exists() {
+      if UnixStream::connect(&path).await.is_ok() {
+        println!("control socket {} is in use by another instance, not listening", path.display());
+        return;
+      }
+      let _ = std::fs::remove_file(&path);
+    }
+    let listener = match UnixListener::bind(&path) {
+      Ok(listener) => listener,
+      Err(e) => {
+        println!("failed to bind control socket {}: {}", path.display(), e);
+        return;
+      }
+    };
+    if let Err(e) = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)) {
+      println!("failed to restrict control socket {}: {}", path.display(), e);
+    }
+    println!("control socket listening on {}", path.display());
+    loop {
+      let stream = match listener.accept().await {
+        Ok((stream, _)) => stream,
+        Err(e) => {
+          println!("control socket accept failed: {}", e);
+          continue;
+        }
+      };
+      let app = app.clone();
+      tokio::task::spawn(async move {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+          let response = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => match execute(&app, command).await {
+              Ok(result) => json!({ "ok": true, "result": result }),
+              Err(error) => json!({ "ok": false, "error": error }),
+            },
+            Err(e) => json!({ "ok": false, "error": format!("invalid command: {}", e) }),
+          };
+          let mut response = response.to_string();
+          response.push('\n');
+          if writer.write_all(response.as_bytes()).await.is_err() {
+            break;
+          }
+        }
+      });
+    }
+  });
+}
+
+#[cfg(not(unix))]
+pub fn spawn<R: Runtime>(_app: AppHandle<R>, _path: PathBuf) {
+  println!("control socket is only supported on unix");
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+async fn execute<R: Runtime>(app: &AppHandle<R>, command: Command) -> Result<Value, Value> {
+  let deno = app.try_state::<DenoManager<R>>().map(|deno| deno.inner().clone()).ok_or_else(|| json!("deno plugin not ready"))?;
+  let to_value = |e: tauri_plugin_deno::Error| serde_json::to_value(e).unwrap_or_default();
+  match command {
+    Command::Send { worker, event, payload } => {
+      let payload = payload.map(|payload| serde_json::from_str(&payload).unwrap_or(Value::String(payload))).unwrap_or_default();
+      deno.emit_to(&worker, &event, payload).await.map_err(to_value)?;
+      Ok(Value::Null)
+    }
+    Command::ListWorkers => Ok(json!(deno.workers_table.keys())),
+    Command::RestartWorker { worker } => {
+      deno.restart_worker(&worker).await.map_err(to_value)?;
+      Ok(Value::Null)
+    }
+  }
+}
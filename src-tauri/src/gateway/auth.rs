@@ -0,0 +1,29 @@
+use ring::hmac;
+
+/// bootstrap.toml 自带的示例秘钥 用它派生的 token 人人可算, 网关拒绝以此启动
+pub const DEFAULT_SECRET: &str = "cassie_admin";
+
+/// token 的派生内容 修改后旧 token 全部失效
+const TOKEN_CONTEXT: &[u8] = b"tauri-cc-gateway";
+
+/// 由 jwt_secret 派生网关 token: hex(HMAC-SHA256(jwt_secret, "tauri-cc-gateway"))
+pub fn derive_token(secret: &str) -> String {
+  let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+  hmac::sign(&key, TOKEN_CONTEXT).as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 常量时间校验 token
+pub fn verify_token(secret: &str, token: &str) -> bool {
+  let Some(tag) = decode_hex(token) else {
+    return false;
+  };
+  let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+  hmac::verify(&key, TOKEN_CONTEXT, &tag).is_ok()
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+  if value.len() % 2 != 0 {
+    return None;
+  }
+  (0..value.len()).step_by(2).map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok()).collect()
+}
@@ -0,0 +1,8 @@
+/**
+ *model: gateway 本地网关 以 http/websocket 暴露 worker 事件和调用
+ *description: 只监听 127.0.0.1, 供配套工具和集成测试在没有 webview 的情况下驱动 deno 后端
+ */
+pub mod auth;
+pub mod server;
+
+pub use server::spawn;
@@ -0,0 +1,238 @@
+use std::{
+  collections::HashMap,
+  net::{Ipv4Addr, SocketAddr},
+};
+
+use axum::{
+  extract::{
+    ws::{Message, WebSocket, WebSocketUpgrade},
+    Path, Query, State,
+  },
+  http::{header::AUTHORIZATION, Request, StatusCode},
+  middleware::{self, Next},
+  response::{IntoResponse, Response},
+  routing::{get, post},
+  Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_deno::{check_reserved, DenoManager, Error as DenoError};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use super::auth::{verify_token, DEFAULT_SECRET};
+
+struct Gateway<R: Runtime> {
+  app: AppHandle<R>,
+  secret: String,
+}
+
+impl<R: Runtime> Clone for Gateway<R> {
+  fn clone(&self) -> Self {
+    Self {
+      app: self.app.clone(),
+      secret: self.secret.clone(),
+    }
+  }
+}
+
+impl<R: Runtime> Gateway<R> {
+  fn deno(&self) -> Result<DenoManager<R>, GatewayError> {
+    self.app.try_state::<DenoManager<R>>().map(|deno| deno.inner().clone()).ok_or(GatewayError::NotReady)
+  }
+}
+
+enum GatewayError {
+  NotReady,
+  Deno(DenoError),
+}
+
+impl From<DenoError> for GatewayError {
+  fn from(e: DenoError) -> Self {
+    GatewayError::Deno(e)
+  }
+}
+
+impl IntoResponse for GatewayError {
+  fn into_response(self) -> Response {
+    match self {
+      GatewayError::NotReady => (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "code": "notReady", "message": "deno plugin not ready" }))).into_response(),
+      GatewayError::Deno(e) => {
+        let status = match e.code() {
+          "workerNotFound" | "targetNotFound" | "channelNotFound" | "streamNotFound" | "subscriptionNotFound" => StatusCode::NOT_FOUND,
+          "permissionDenied" => StatusCode::FORBIDDEN,
+          "timeout" => StatusCode::GATEWAY_TIMEOUT,
+          "invalidSchema" | "invalidPayload" | "json" => StatusCode::BAD_REQUEST,
+          _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(e)).into_response()
+      }
+    }
+  }
+}
+
+/// 在 127.0.0.1:port 启动网关 token 由 jwt_secret 派生, 见 auth::derive_token
+/// 所有请求需带 `Authorization: Bearer <token>`, websocket 可改用 `?token=<token>`
+/// jwt_secret 为空或仍是默认值时不启动
+pub fn spawn<R: Runtime>(app: AppHandle<R>, port: u16, secret: String) {
+  if secret.is_empty() {
+    println!("gateway disabled: jwt_secret is empty");
+    return;
+  }
+  if secret == DEFAULT_SECRET {
+    println!("gateway disabled: jwt_secret is still the default, set your own in bootstrap.toml");
+    return;
+  }
+  let gateway = Gateway { app, secret };
+  let router = Router::new()
+    .route("/health", get(health::<R>))
+    .route("/workers", get(workers::<R>))
+    .route("/workers/:worker/events/:event", post(emit::<R>))
+    .route("/workers/:worker/call/:method", post(call::<R>))
+    .route("/ws", get(upgrade::<R>))
+    .route_layer(middleware::from_fn_with_state(gateway.clone(), authorize::<R, axum::body::Body>))
+    .with_state(gateway);
+  //只监听回环地址 不对外暴露
+  let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+  tauri::async_runtime::spawn(async move {
+    //端口被占用时 bind 会直接 panic, 用 try_bind 记录错误后退出
+    let server = match axum::Server::try_bind(&addr) {
+      Ok(server) => server,
+      Err(e) => {
+        println!("gateway failed to listen on {}: {}", addr, e);
+        return;
+      }
+    };
+    println!("gateway listening on http://{}", addr);
+    if let Err(e) = server.serve(router.into_make_service()).await {
+      println!("gateway stopped: {}", e);
+    }
+  });
+}
+
+async fn authorize<R: Runtime, B>(State(gateway): State<Gateway<R>>, Query(query): Query<HashMap<String, String>>, request: Request<B>, next: Next<B>) -> Response {
+  let header = request.headers().get(AUTHORIZATION).and_then(|value| value.to_str().ok()).and_then(|value| value.strip_prefix("Bearer "));
+  let token = header.or(query.get("token").map(String::as_str));
+  match token {
+    Some(token) if verify_token(&gateway.secret, token) => next.run(request).await,
+    _ => (StatusCode::UNAUTHORIZED, Json(json!({ "code": "unauthorized", "message": "invalid gateway token" }))).into_response(),
+  }
+}
+
+async fn health<R: Runtime>(State(gateway): State<Gateway<R>>) -> Result<Json<Value>, GatewayError> {
+  let deno = gateway.deno()?;
+  Ok(Json(json!({ "router": deno.router.health(), "workers": deno.workers_table.keys() })))
+}
+
+async fn workers<R: Runtime>(State(gateway): State<Gateway<R>>) -> Result<Json<Vec<String>>, GatewayError> {
+  Ok(Json(gateway.deno()?.workers_table.keys()))
+}
+
+/// 向 worker 发送事件 与 webview 经通道发送的消息相同, 只是没有来源外层; 插件保留的 deno: 事件返回 403
+async fn emit<R: Runtime>(State(gateway): State<Gateway<R>>, Path((worker, event)): Path<(String, String)>, Json(payload): Json<Value>) -> Result<StatusCode, GatewayError> {
+  check_reserved(&event)?;
+  gateway.deno()?.emit_to(&worker, &event, payload).await?;
+  Ok(StatusCode::NO_CONTENT)
+}
+
+/// 调用 worker 中以 handle 注册的方法
+async fn call<R: Runtime>(State(gateway): State<Gateway<R>>, Path((worker, method)): Path<(String, String)>, Json(args): Json<Value>) -> Result<Json<Value>, GatewayError> {
+  let result: Value = gateway.deno()?.call(&worker, &method, args).await?;
+  Ok(Json(result))
+}
+
+async fn upgrade<R: Runtime>(State(gateway): State<Gateway<R>>, ws: WebSocketUpgrade) -> Result<Response, GatewayError> {
+  let deno = gateway.deno()?;
+  Ok(ws.on_upgrade(move |socket| session(deno, socket)))
+}
+
+/// websocket 请求 回复均为 json 文本帧
+/// {"type":"emit", worker, event, payload}
+/// {"type":"call", id, worker, method, args} -> {"type":"reply", id, result | error}
+/// {"type":"subscribe", worker, event} -> {"type":"event", worker, event, payload} 订阅 worker 发往窗口的事件
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum WsRequest {
+  Emit {
+    worker: String,
+    event: String,
+    #[serde(default)]
+    payload: Value,
+  },
+  Call {
+    id: Value,
+    worker: String,
+    method: String,
+    #[serde(default)]
+    args: Value,
+  },
+  Subscribe {
+    worker: String,
+    event: String,
+  },
+}
+
+async fn session<R: Runtime>(deno: DenoManager<R>, mut socket: WebSocket) {
+  let (sender, mut receiver) = mpsc::unbounded_channel::<Value>();
+  //连接断开时结束该连接的订阅
+  let mut subscriptions: Vec<JoinHandle<()>> = Vec::new();
+  loop {
+    tokio::select! {
+      message = socket.recv() => {
+        let text = match message {
+          Some(Ok(Message::Text(text))) => text,
+          Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+          Some(Ok(_)) => continue,
+        };
+        let request = match serde_json::from_str::<WsRequest>(&text) {
+          Ok(request) => request,
+          Err(e) => {
+            let _ = sender.send(json!({ "type": "error", "message": format!("invalid request: {}", e) }));
+            continue;
+          }
+        };
+        match request {
+          WsRequest::Emit { worker, event, payload } => {
+            let result = match check_reserved(&event) {
+              Ok(()) => deno.emit_to(&worker, &event, payload).await,
+              Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+              let _ = sender.send(json!({ "type": "error", "error": e }));
+            }
+          }
+          WsRequest::Call { id, worker, method, args } => {
+            let deno = deno.clone();
+            let sender = sender.clone();
+            tokio::task::spawn(async move {
+              let reply = match deno.call::<Value, Value>(&worker, &method, args).await {
+                Ok(result) => json!({ "type": "reply", "id": id, "result": result }),
+                Err(e) => json!({ "type": "reply", "id": id, "error": e }),
+              };
+              let _ = sender.send(reply);
+            });
+          }
+          WsRequest::Subscribe { worker, event } => {
+            let mut subscription = deno.subscribe(&worker, &event);
+            let sender = sender.clone();
+            subscriptions.push(tokio::task::spawn(async move {
+              while let Some(payload) = subscription.recv().await {
+                if sender.send(json!({ "type": "event", "worker": worker, "event": event, "payload": payload })).is_err() {
+                  break;
+                }
+              }
+            }));
+          }
+        }
+      }
+      Some(reply) = receiver.recv() => {
+        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+          break;
+        }
+      }
+    }
+  }
+  for subscription in subscriptions {
+    subscription.abort();
+  }
+}
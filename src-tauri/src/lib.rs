@@ -2,6 +2,8 @@
 extern crate getset;
 
 pub mod config;
+pub mod control;
+pub mod gateway;
 pub mod initialize;
 
 use crate::initialize::config::init_config;
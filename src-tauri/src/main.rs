@@ -1,39 +1,65 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use clap::Parser;
 use tauri::Manager;
 use tauri_desktop::config::config::ApplicationConfig;
+use tauri_desktop::config::option::Opt;
+use tauri_desktop::control;
+use tauri_desktop::gateway;
 use tauri_desktop::init_context;
 use tauri_desktop::APPLICATION_CONTEXT;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+  //带子命令时作为客户端操作正在运行的实例
+  let opt = Opt::parse();
+  let socket_path = control::socket_path(&opt.control_socket);
+  if let Some(command) = opt.command {
+    return control::run_command(&socket_path, command).await;
+  }
   init_context().await;
   let ref_app_config = APPLICATION_CONTEXT.get::<ApplicationConfig>();
   let mut build = tauri::Builder::default();
-  build = build.setup(|app| {
+  build = build.setup(move |app| {
     #[cfg(debug_assertions)] //仅在调试时自动打开开发者工具
     {
       let main_window = app.get_webview_window("main").unwrap();
       main_window.open_devtools();
     }
+    //本地网关 供配套工具和集成测试驱动 deno 后端
+    let app_config = APPLICATION_CONTEXT.get::<ApplicationConfig>();
+    if let (true, Some(port)) = (*app_config.server().gateway(), *app_config.server().port()) {
+      gateway::spawn(app.handle().clone(), port, app_config.jwt_secret().clone());
+    }
+    if *app_config.server().control() {
+      control::spawn(app.handle().clone(), socket_path);
+    }
     Ok(())
   });
   #[cfg(not(debug_assertions))]
   {
     let path = ref_app_config.pro_code_path();
-    build = build.plugin(tauri_plugin_deno::init(path.into()));
+    build = build.plugin(deno_plugin(path.into()));
   }
   #[cfg(debug_assertions)]
   {
     let path = ref_app_config.dev_code_path();
-    build = build.plugin(tauri_plugin_deno::init(path.into())).plugin(tauri_plugin_devtools::init());
+    build = build.plugin(deno_plugin(path.into())).plugin(tauri_plugin_devtools::init());
   }
   build = build.invoke_handler(tauri::generate_handler![sync_message, async_message]);
   build.run(tauri::generate_context!()).expect("error while running tauri application");
   Ok(())
 }
 
+//deno 插件 注册 worker 可通过 callHost 调用的 rust 命令
+fn deno_plugin<R: tauri::Runtime>(path: String) -> tauri::plugin::TauriPlugin<R> {
+  tauri_plugin_deno::Builder::new(path)
+    .command("sync_message", |invoke_message: String| async move { sync_message(invoke_message).map_err(|_| "sync_message failed") })
+    .fetch_permission("main", "main")
+    .build()
+}
+
 //同步消息
 
 #[tauri::command(rename_all = "snake_case")]